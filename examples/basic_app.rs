@@ -1,16 +1,16 @@
 use std::env;
-use suihorse::App;
+use suihorse::{App, Context};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    let app = App::new()
+    let app = App::new("basic_app")
         .usage("single_app [args]")
         .action(action);
 
     app.run(args);
 }
 
-fn action(args: Vec<String>) {
-    println!("Hello, {:?}", args);
+fn action(c: &Context) {
+    println!("Hello, {:?}", c.args);
 }
@@ -0,0 +1,27 @@
+use std::env;
+use suihorse::{Command, Context, Flag, FlagType};
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let command = Command::new("greet")
+        .description("greet people by name")
+        .usage("greet [--loud] [names]...")
+        .flag(
+            Flag::new("loud", FlagType::Bool)
+                .alias("l")
+                .description("shout the greeting"),
+        )
+        .action(action);
+
+    command.run(args);
+}
+
+fn action(c: &Context) {
+    let greeting = format!("Hello, {}", c.args.join(", "));
+    if c.bool_flag("loud") {
+        println!("{}!", greeting.to_uppercase());
+    } else {
+        println!("{}", greeting);
+    }
+}
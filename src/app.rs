@@ -1,41 +1,281 @@
-use crate::{Command, Help};
+use crate::error::{ActionError, ActionErrorKind, ExitCode};
+use crate::command::{check_registration, registration_panic, CommandIndex, Dispatch, Output};
+use crate::help::{generated_app_usage, DEFAULT_HELP_FLAGS};
+use crate::parse::{
+    bool_assignments, env_file_vars, expanded_response_files, expanded_short_flags, flag_args,
+    trimmed_args,
+};
+#[cfg(feature = "config-file")]
+use crate::config_file;
+use crate::{completion, Command, Context, Flag, FlagType, Help, HelpBuilder, Outcome};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::error::Error;
+use std::ffi::OsString;
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{fmt, fs, iter, process, slice};
 
-/// Command and application action type
+/// Command and application action type, `Send` and `Sync` so that an app
+/// may be shared across threads
 ///
 /// Example
 ///
 /// ```
-/// use suihorse::{Action, Context};
+/// use std::sync::Arc;
+/// use suihorse::{Action, App, Context};
 ///
-/// let action: Action = |c: &Context| {
+/// let action: Action = Arc::new(|c: &Context| {
 ///     println!("{:?}", c.args);
-/// };
+///     Ok(())
+/// });
+///
+/// fn shareable<T: Send + Sync>(_: &T) {}
+/// shareable(&App::new("cli").action(|_| {}));
 /// ```
-pub type Action = fn(Vec<String>);
+pub type Action = Arc<dyn Fn(&Context) -> Result<(), Box<dyn Error>> + Send + Sync>;
+
+/// Invoke `action`, converting a panic into a `Panicked` error when `catch_unwind`
+pub(crate) fn invoke(
+    action: &Action,
+    context: &Context,
+    catch_unwind: bool,
+) -> Result<(), Box<dyn Error>> {
+    if !catch_unwind {
+        return action(context);
+    }
+
+    panic::catch_unwind(AssertUnwindSafe(|| action(context))).unwrap_or_else(|payload| {
+        let message = match payload.downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => match payload.downcast_ref::<String>() {
+                Some(message) => message.to_owned(),
+                None => "unknown panic payload".to_string(),
+            },
+        };
+        Err(Box::new(ActionError {
+            kind: ActionErrorKind::Panicked(message),
+        }))
+    })
+}
+
+/// Index the sub commands of `command`, at `path` from the top of the app,
+/// and their own sub commands
+fn index_sub_commands(index: &mut CommandIndex, path: Vec<String>, command: &Command) {
+    let sub_commands = command.commands.as_deref().unwrap_or(&[]);
+    if sub_commands.is_empty() {
+        return;
+    }
+
+    let positions = index.entry(path.clone()).or_default();
+    for (position, c) in sub_commands.iter().enumerate() {
+        for name in iter::once(&c.name).chain(c.alias.iter().flatten()) {
+            positions.entry(name.to_owned()).or_insert(position);
+        }
+    }
+    for c in sub_commands {
+        let mut sub_path = path.clone();
+        sub_path.push(c.name.clone());
+        index_sub_commands(index, sub_path, c);
+    }
+}
+
+/// Number of single character insertions, deletions, substitutions and
+/// transpositions of adjacent characters turning `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // distances from the prefixes of `a` to the prefixes of `b`,
+    // for the current row and the two before it
+    let mut before: Vec<usize> = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        before = previous;
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Env file read by `App::dotenv`, relative to the working directory
+const DOTENV_PATH: &str = ".env";
+
+/// Summary of an app run returned by `App::run_logged`
+#[derive(Clone, Debug)]
+pub struct Invocation {
+    /// Names of the matched commands, empty when the app action ran
+    pub command_path: Vec<String>,
+    /// What the run did
+    pub outcome: Outcome,
+    /// Time spent running
+    pub duration: Duration,
+}
 
 /// Multiple action application entry point
+///
+/// `Debug` lists command and flag names, actions print as `<action>`
+///
+/// ```
+/// use suihorse::{App, Command};
+///
+/// let app = App::new("cli")
+///     .command(Command::new("build").alias("b").action(|_| println!("build")))
+///     .command(Command::new("test"));
+///
+/// assert!(format!("{:?}", app).contains(r#"commands: ["build", "test"], action: None"#));
+/// assert!(format!("{:?}", app.commands[0]).contains(r#"alias: Some(["b"])"#));
+/// assert!(format!("{:?}", app.commands[0]).contains("action: Some(<action>)"));
+/// ```
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct App {
-    /// usage: "cli [command] [arg]"
-    pub usage: String,
-    /// Application commands including default cmds and dev defined
+    /// Application name, heading the help
+    pub name: String,
+    /// Application author, shown under the name in help
+    pub author: Option<String>,
+    /// Application version, shown next to the name in help
+    pub version: Option<String>,
+    /// Application description
+    pub description: Option<String>,
+    /// Explicit usage overriding the one synthesized by `usage_text`
+    pub usage: Option<String>,
+    /// Application commands including default cmds and dev defined.
+    /// Register them with `command`, commands pushed here directly are only
+    /// found by scanning every command.
     pub commands: Vec<Command>,
-    /// default action displaying recent data and config
-    pub action: Action,
+    /// Action run when no command matches, the help is shown when `None`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub action: Option<Action>,
+    /// Application flags
+    pub flags: Option<Vec<Flag>>,
+    /// Flags parsed by every command and the app action, listed in every help
+    pub global_flags: Option<Vec<Flag>>,
+    /// Accept a global `--config-dump` flag printing the resolved flags
+    pub config_dump: bool,
+    /// `KEY=VALUE` file supplementing env for flag env fallbacks
+    pub env_file: Option<String>,
+    /// Read `.env` in the working directory when no `env_file` is set
+    pub dotenv: bool,
+    /// Prefix of the env variable read for every flag without a value on the command line
+    pub env_prefix: Option<String>,
+    /// TOML file of flag values, used when neither the command line nor env sets them
+    #[cfg(feature = "config-file")]
+    pub config_file: Option<String>,
+    /// Env variable silencing the warning of deprecated commands when set
+    pub no_deprecation_warnings_env: Option<String>,
+    /// Dispatch a unique prefix of a command name or alias to that command
+    pub prefix_matching: bool,
+    /// Match command names and aliases regardless of case
+    pub case_insensitive: bool,
+    /// Return an error for a first token matching no command
+    /// instead of running the app action
+    pub strict: bool,
+    /// Tokens treated as help triggers, "-h" and "--help" by default
+    pub help_flags: Vec<String>,
+    /// Exit code returned by `run_and_exit_code` when the run fails, 1 by default
+    pub error_exit_code: i32,
+    /// Synthesize the usage shown in help for commands without one, on by default
+    pub auto_usage: bool,
+    /// Return a `Panicked` error instead of unwinding when an action panics
+    pub catch_unwind: bool,
+    /// Expand `@path` args to the args read from the file at `path`
+    pub response_files: bool,
+    /// Return a `NoAction` error for args given to a command without action
+    pub no_action_error: bool,
+    /// Accept unique prefixes of long flags, such as `--verb` for `--verbose`
+    pub abbreviate_flags: bool,
+    /// Accept Windows style `/name` and `/name:value` for declared flags
+    pub windows_flags: bool,
+    /// Run the command named like the binary in `argv[0]`, busybox style
+    pub dispatch_by_program_name: bool,
+    /// Return a `NoCommand` error after the help when no command matches and
+    /// there is no action
+    pub no_command_error: bool,
+    /// Fail with every `--flag` matching no declared flag instead of keeping them in args
+    pub deny_unknown_flags: bool,
+    /// Show help instead of running the action when no arg is given
+    pub no_args_is_help: bool,
+    /// Command run with every arg when the first one matches no command
+    pub default_command: Option<String>,
+    /// Positions in `commands` by command name and alias, for the commands set
+    /// with `command`, so that exact matches don't scan every command
+    #[cfg_attr(feature = "serde", serde(skip))]
+    command_index: HashMap<String, usize>,
+    /// Positions of the sub commands of the commands set with `command`,
+    /// by path of command names, then by sub command name and alias
+    #[cfg_attr(feature = "serde", serde(skip))]
+    sub_command_index: CommandIndex,
+}
+
+impl fmt::Debug for App {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let flag_names: Vec<&String> = self.flags.iter().flatten().map(|f| &f.name).collect();
+        f.debug_struct("App")
+            .field("name", &self.name)
+            .field("version", &self.version)
+            .field("description", &self.description)
+            .field("usage", &self.usage)
+            .field("commands", &self.command_names())
+            .field("action", &self.action.as_ref().map(|_| format_args!("<action>")))
+            .field("flags", &flag_names)
+            .finish()
+    }
 }
-// TODO add default action and commands 
+
 impl Default for App {
     fn default() -> Self {
         Self {
-            usage: "cli [command] [arg]".to_string(),
+            name: "cli".to_string(),
+            author: None,
+            version: None,
+            description: None,
+            usage: None,
             commands: vec![],
-            action: |_| { println!("j") },
+            action: None,
+            flags: None,
+            global_flags: None,
+            config_dump: false,
+            env_file: None,
+            dotenv: false,
+            env_prefix: None,
+            #[cfg(feature = "config-file")]
+            config_file: None,
+            no_deprecation_warnings_env: None,
+            prefix_matching: false,
+            case_insensitive: false,
+            strict: false,
+            help_flags: DEFAULT_HELP_FLAGS.iter().map(|f| f.to_string()).collect(),
+            error_exit_code: 1,
+            auto_usage: true,
+            catch_unwind: false,
+            response_files: false,
+            no_action_error: false,
+            abbreviate_flags: false,
+            windows_flags: false,
+            dispatch_by_program_name: false,
+            no_command_error: false,
+            deny_unknown_flags: false,
+            no_args_is_help: true,
+            default_command: None,
+            command_index: HashMap::new(),
+            sub_command_index: HashMap::new(),
         }
     }
 }
 
 impl App {
-    /// Create new instance of `App`
+    /// Create new instance of `App` named `name`, the synthesized usage starts with it
     ///
     /// Example
     ///
@@ -43,23 +283,110 @@ impl App {
     /// use suihorse::App;
     ///
     /// let app = App::new("cli");
+    /// assert_eq!(app.usage_text(), "cli");
+    /// ```
+    pub fn new<T: Into<String>>(name: T) -> Self {
+        Self {
+            name: name.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Set author of the app, shown under its name in help
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Help};
+    ///
+    /// let app = App::new("cli")
+    ///     .version("1.2.0")
+    ///     .author("Jane Doe <jane@example.com>")
+    ///     .description("cli to interact with my package");
+    ///
+    /// assert!(app.help_text().starts_with(concat!(
+    ///     "cli 1.2.0\n",
+    ///     "Jane Doe <jane@example.com>\n",
+    ///     "cli to interact with my package\n\n",
+    ///     "Usage:\n",
+    /// )));
     /// ```
-    pub fn new() -> Self {
-        Self::default()
+    pub fn author<T: Into<String>>(mut self, author: T) -> Self {
+        self.author = Some(author.into());
+        self
     }
 
-    /// Set usage of the app
+    /// Set version of the app, shown next to its name in help.
+    /// `--version` and `-V` then print `<name> <version>` instead of running
+    /// the app or a command action, unless a flag of theirs is named so.
+    /// They are listed after help in the `Flags:` sections.
     ///
     /// Example
     ///
     /// ```
-    /// use seahorse::App;
+    /// use suihorse::{App, Command, Flag, FlagType, Help, Outcome};
     ///
-    /// let app = App::new("cli");
-    /// app.usage("cli [command] [arg]");
+    /// let app = App::new("cli")
+    ///     .version("1.2.0")
+    ///     .command(Command::new("build").action(|_| unreachable!()))
+    ///     .command(
+    ///         Command::new("sort")
+    ///             .flag(Flag::new("reverse", FlagType::Bool).short('V'))
+    ///             .action(|c| assert!(c.bool_flag("reverse"))),
+    ///     )
+    ///     .global_bool_flag("verbose")
+    ///     .action(|_| unreachable!());
+    /// assert!(app.help_text().starts_with("cli 1.2.0\n\nUsage:\n"));
+    /// assert!(app.help_text().contains("\t-V, --version : Print version\n"));
+    /// assert!(app.command_help_text("cli", &["build"]).unwrap().contains("-V, --version"));
+    /// assert!(!app.command_help_text("cli", &["sort"]).unwrap().contains("--version"));
+    ///
+    /// // prints "cli 1.2.0"
+    /// let args = vec!["cli", "--version"];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert_eq!(invocation.outcome, Outcome::VersionShown);
+    ///
+    /// let args = vec!["cli", "build", "-V"];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert_eq!(invocation.outcome, Outcome::VersionShown);
+    ///
+    /// let args = vec!["cli", "sort", "-V"];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert_eq!(invocation.outcome, Outcome::ActionRan);
+    /// ```
+    pub fn version<T: Into<String>>(mut self, version: T) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Set description of the app, shown above usage in help
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::App;
+    ///
+    /// let app = App::new("cli")
+    ///     .description("cli to interact with my package");
+    /// ```
+    pub fn description<T: Into<String>>(mut self, description: T) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Set usage of the app, overriding the one synthesized from its commands and flags
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::App;
+    ///
+    /// let app = App::new("cli")
+    ///     .usage("cli [command] [arg]");
+    /// assert_eq!(app.usage_text(), "cli [command] [arg]");
     /// ```
     pub fn usage<T: Into<String>>(mut self, usage: T) -> Self {
-        self.usage = usage.into();
+        self.usage = Some(usage.into());
         self
     }
 
@@ -97,155 +424,2054 @@ impl App {
     ///     .command(command1)
     ///     .command(command2);
     /// ```
-    pub fn command(mut self, command: Command) -> Self {
-        if self.commands
-            .iter()
-            .any(|registered| registered.name == command.name)
-        {
-            panic!(r#"Command name "{}" is already registered."#, command.name);
-        }
-        self.commands.push(command);
-        self
+    ///
+    /// You cannot set a command with an empty name.
+    ///
+    /// ```should_panic
+    /// use suihorse::{App, Command};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new(""));
+    /// ```
+    ///
+    /// Names and aliases are unique across commands, so none is unreachable.
+    ///
+    /// ```should_panic
+    /// use suihorse::{App, Command};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build").alias("b"))
+    ///     .command(Command::new("bench").alias("b"));
+    /// ```
+    pub fn command(self, command: Command) -> Self {
+        self.try_command(command).unwrap_or_else(|e| registration_panic(e))
     }
 
-    /// Set action of the app
+    /// Set every command of `commands`, such as a `Vec<Command>` from a plugin loader
     ///
     /// Example
     ///
     /// ```
-    /// use suihorse::{Action, App, Context};
+    /// use suihorse::{App, Command};
     ///
-    /// let action: Action = |c: &Context| println!("{:?}", c.args);
+    /// let commands = vec![Command::new("build"), Command::new("test")];
     /// let app = App::new("cli")
-    ///     .action(action);
+    ///     .commands_iter(commands);
+    /// assert_eq!(app.command_names(), vec!["build", "test"]);
     /// ```
-    pub fn action(mut self, action: Action) -> Self {
-        self.action = action;
-        self
+    ///
+    /// # Panics
+    ///
+    /// Like `command`, on the first empty or already registered name.
+    ///
+    /// ```should_panic
+    /// use suihorse::{App, Command};
+    ///
+    /// let app = App::new("cli")
+    ///     .commands_iter(vec![Command::new("build"), Command::new("build")]);
+    /// ```
+    pub fn commands_iter<I: IntoIterator<Item = Command>>(self, commands: I) -> Self {
+        commands.into_iter().fold(self, |app, command| app.command(command))
     }
 
-    /// Run app
+    /// Set command of the app only when `condition` holds, such as a feature
+    /// enabled at runtime
     ///
     /// Example
     ///
     /// ```
-    /// use std::env;
-    /// use suihorse::App;
+    /// use suihorse::{App, Command};
     ///
-    /// let args: Vec<String> = env::args().collect();
-    /// let app = App::new("cli");
-    /// app.run(args);
+    /// let app = App::new("cli")
+    ///     .command_if(true, Command::new("build"))
+    ///     .command_if(false, Command::new("experimental"));
+    /// assert_eq!(app.command_names(), vec!["build"]);
     /// ```
-    pub fn run(&self, args: Vec<String>) {
-        match self.run_with_result(args) {
-            Ok(_) => return,
-            Err(e) => panic!("{}", e),
+    ///
+    /// # Panics
+    ///
+    /// Like `command`, when `condition` holds.
+    pub fn command_if(self, condition: bool, command: Command) -> Self {
+        if condition {
+            self.command(command)
+        } else {
+            self
         }
     }
 
-    /// Run app, returning a result
+    /// Set every command returned by `commands`, which is called right away
+    /// so duplicates are caught while building the app
     ///
     /// Example
     ///
     /// ```
-    /// use std::env;
-    /// use suihorse::App;
+    /// use suihorse::{App, Command};
     ///
-    /// let args: Vec<String> = env::args().collect();
-    /// let app = App::new("cli");
-    /// let result = app.run_with_result(args);
+    /// let plugins_enabled = true;
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build"))
+    ///     .commands_from(|| {
+    ///         if plugins_enabled {
+    ///             vec![Command::new("lint"), Command::new("fmt")]
+    ///         } else {
+    ///             vec![]
+    ///         }
+    ///     });
+    /// assert_eq!(app.command_names(), vec!["build", "lint", "fmt"]);
     /// ```
-    pub fn run_with_result(&self, args: Vec<String>) -> Result<(), Box<dyn Error>> {
-        let args = Self::normalized_args(args);
-        let (cmd_v, args_v) = args.split_at(1);
-        let cmd = cmd_v.first().unwrap();
-        
-        // gets the command in the App that matches `cmd` or return None
-        let command = self.commands.iter().find(|command| match &command.alias {
-            Some(alias) => &command.name == cmd || alias.iter().any(|a| a == cmd),
-            None => &command.name == cmd,
-        });
+    ///
+    /// # Panics
+    ///
+    /// Like `command`, on the first empty or already registered name.
+    ///
+    /// ```should_panic
+    /// use suihorse::{App, Command};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build"))
+    ///     .commands_from(|| vec![Command::new("build")]);
+    /// ```
+    pub fn commands_from<F: FnOnce() -> Vec<Command>>(self, commands: F) -> Self {
+        self.commands_iter(commands())
+    }
 
-        match command {
-            // if there is a command we run it
-            Some(command) => return command.run_with_result(args_v.to_vec()),
-            // if the 2nd arg is not a command we run App action
-            None => {
-                // except if there's a help flag
-                if args.contains(&"-h".to_string()) || args.contains(&"--help".to_string()) {
-                    self.help();
-                    return Ok(());
-                };
-                let action = self.action;
-                action(args[1..].to_vec());
-                return Ok(());
-            }
+    /// Set command of the app, returning a `Duplicate` error instead of
+    /// panicking when the name is already registered, an `AliasCollision`
+    /// error when its name or an alias is the name or an alias of another command,
+    /// or an `EmptyName` error when its name is empty
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::error::ActionErrorKind;
+    /// use suihorse::{App, Command};
+    ///
+    /// let app = App::new("cli")
+    ///     .try_command(Command::new("build"))
+    ///     .unwrap();
+    ///
+    /// let error = app.try_command(Command::new("build")).err().unwrap();
+    /// assert_eq!(error.kind, ActionErrorKind::Duplicate("build".to_string()));
+    ///
+    /// let app = || {
+    ///     App::new("cli")
+    ///         .command(Command::new("build"))
+    ///         .command(Command::new("test").alias("t"))
+    /// };
+    /// let error = app().try_command(Command::new("tidy").alias("t")).err().unwrap();
+    /// assert_eq!(
+    ///     error.kind,
+    ///     ActionErrorKind::AliasCollision("tidy".to_string(), "t".to_string(), "test".to_string())
+    /// );
+    /// let error = app().try_command(Command::new("compile").alias("build")).err().unwrap();
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     r#"AliasCollision: "build" of command "compile" is already used by command "build""#
+    /// );
+    ///
+    /// let error = app().try_command(Command::new("")).err().unwrap();
+    /// assert_eq!(error.kind, ActionErrorKind::EmptyName);
+    /// assert_eq!(error.to_string(), "EmptyName: command name must not be empty");
+    /// ```
+    pub fn try_command(mut self, command: Command) -> Result<Self, ActionError> {
+        check_registration(&self.commands, &command)?;
+
+        let index = self.commands.len();
+        for name in iter::once(&command.name).chain(command.alias.iter().flatten()) {
+            self.command_index.entry(name.to_owned()).or_insert(index);
         }
+        index_sub_commands(&mut self.sub_command_index, vec![command.name.clone()], &command);
+        self.commands.push(command);
+        Ok(self)
     }
 
-    /// Split arg with "=" to unify arg notations.
-    /// --flag=value => ["--flag", "value"]
-    /// --flag value => ["--flag", "value"]
-    fn normalized_args(raw_args: Vec<String>) -> Vec<String> {
-        raw_args.iter().fold(Vec::<String>::new(), |mut acc, cur| {
-            if cur.starts_with('-') && cur.contains('=') {
-                let mut splitted_flag: Vec<String> =
-                    cur.splitn(2, '=').map(|s| s.to_owned()).collect();
-                acc.append(&mut splitted_flag);
-            } else {
-                acc.push(cur.to_owned());
-            }
-            acc
-        })
+    /// Set action of the app, closures may capture their environment
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    /// use suihorse::{App, Context};
+    ///
+    /// let calls = Arc::new(AtomicUsize::new(0));
+    /// let counter = Arc::clone(&calls);
+    /// let app = App::new("cli")
+    ///     .action(move |c: &Context| {
+    ///         counter.fetch_add(c.args.len(), Ordering::SeqCst);
+    ///     });
+    ///
+    /// let args = vec!["cli", "a", "b"];
+    /// app.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert_eq!(calls.load(Ordering::SeqCst), 2);
+    /// ```
+    pub fn action<F: Fn(&Context) + Send + Sync + 'static>(mut self, action: F) -> Self {
+        self.action = Some(Arc::new(move |c| {
+            action(c);
+            Ok(())
+        }));
+        self
     }
 
-    fn command_help_text(&self) -> String {
-        let mut text = String::new();
-
-        text += "\nCommands:\n";
-
-        let name_max_len = &self.commands
-            .iter()
-            .map(|c| {
-                if let Some(alias) = &c.alias {
-                    format!("{}, {}", alias.join(", "), c.name).len()
-                } else {
-                    c.name.len()
-                }
-            })
-            .max()
-            .unwrap();
+    /// Set fallible action of the app, an error it returns is returned by `run_with_result`
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::App;
+    ///
+    /// let app = App::new("cli")
+    ///     .action_with_result(|c| {
+    ///         let count: u32 = c.args[0].parse()?;
+    ///         println!("{}", count);
+    ///         Ok(())
+    ///     });
+    ///
+    /// let args = vec!["cli", "two"];
+    /// assert!(app.run_with_result(args.into_iter().map(String::from).collect()).is_err());
+    /// ```
+    pub fn action_with_result<F>(mut self, action: F) -> Self
+    where
+        F: Fn(&Context) -> Result<(), Box<dyn Error>> + Send + Sync + 'static,
+    {
+        self.action = Some(Arc::new(action));
+        self
+    }
 
-        for c in self.commands.iter() {
-            let command_name = if let Some(alias) = &c.alias {
-                format!("{}, {}", alias.join(", "), c.name)
-            } else {
-                c.name.clone()
-            };
+    /// Dispatch a unique prefix of a command name or alias to that command,
+    /// an ambiguous prefix is an error listing every matching command.
+    /// Exact matches always win, off by default.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("status").action(|_| println!("status")))
+    ///     .command(Command::new("start").action(|_| println!("start")))
+    ///     .prefix_matching(true);
+    ///
+    /// let args = vec!["cli", "stat"];
+    /// assert!(app.run_with_result(args.into_iter().map(String::from).collect()).is_ok());
+    ///
+    /// let args = vec!["cli", "sta"];
+    /// let error = app.run_with_result(args.into_iter().map(String::from).collect());
+    /// let message = error.unwrap_err().to_string();
+    /// assert!(message.contains("status") && message.contains("start"));
+    /// ```
+    pub fn prefix_matching(mut self, prefix_matching: bool) -> Self {
+        self.prefix_matching = prefix_matching;
+        self
+    }
 
-            let description = match &c.description {
-                Some(description) => description,
-                None => "",
-            };
+    /// Match command names and aliases regardless of case, off by default
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build").action(|_| println!("build")))
+    ///     .action(|_| unreachable!())
+    ///     .case_insensitive(true);
+    ///
+    /// let args = vec!["cli", "BUILD"];
+    /// app.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// ```
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
 
-            text += &format!(
-                "\t{} {}: {}\n",
-                command_name,
-                " ".repeat(name_max_len - command_name.len()),
-                description
-            );
-        }
+    /// Return an `UnknownCommand` error, echoing the token as typed, when the first
+    /// arg matches no command instead of running the app action. Off by default.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build").action(|_| println!("build")))
+    ///     .action(|_| unreachable!())
+    ///     .case_insensitive(true)
+    ///     .strict(true);
+    ///
+    /// let args = vec!["cli", "Deploy"];
+    /// let error = app.run_with_result(args.into_iter().map(String::from).collect());
+    /// assert!(error.unwrap_err().to_string().contains(r#""Deploy""#));
+    /// ```
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
 
-        text
+    /// Override the tokens treated as help triggers by the app and its commands,
+    /// listed as the help row of the `Flags:` sections
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Flag, FlagType, Help, Outcome};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(
+    ///         Command::new("build")
+    ///             .flag(Flag::new("release", FlagType::Bool))
+    ///             .action(|c| assert_eq!(c.args, vec!["-h"])),
+    ///     )
+    ///     .flag(Flag::new("verbose", FlagType::Bool))
+    ///     .help_flags(&["-?", "--help"]);
+    ///
+    /// assert!(app.help_text().contains("\t-?, --help : Print help\n"));
+    /// let text = app.command_help_text("cli", &["build"]).unwrap();
+    /// assert!(text.contains("\t-?, --help : Print help\n") && !text.contains("-h,"));
+    ///
+    /// let args = vec!["cli", "build", "-?"];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert_eq!(invocation.outcome, Outcome::HelpShown);
+    ///
+    /// let args = vec!["cli", "build", "-h"];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert_eq!(invocation.outcome, Outcome::ActionRan);
+    /// ```
+    pub fn help_flags(mut self, help_flags: &[&str]) -> Self {
+        self.help_flags = help_flags.iter().map(|f| f.to_string()).collect();
+        self
     }
-}
 
-impl Help for App {
-    fn help_text(&self) -> String {
-        let mut text = String::new();
-        text += &format!("Usage:\n\t{}\n\n", self.usage);
-        text += &self.command_help_text();
+    /// Synthesize the usage of commands without an explicit one from the binary
+    /// name, the command names and the flags, such as `myapp build [--verbose]`.
+    /// On by default, commands without usage show none when off.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Flag, FlagType};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build").flag(Flag::new("verbose", FlagType::Bool)))
+    ///     .command(Command::new("test").usage("myapp test <name>"));
+    ///
+    /// assert_eq!(app.command_usage("myapp", "build").unwrap(), "myapp build [--verbose]");
+    /// assert_eq!(app.command_usage("myapp", "test").unwrap(), "myapp test <name>");
+    ///
+    /// let app = app.auto_usage(false);
+    /// assert!(app.command_usage("myapp", "build").is_none());
+    /// ```
+    pub fn auto_usage(mut self, auto_usage: bool) -> Self {
+        self.auto_usage = auto_usage;
+        self
+    }
 
-        text
+    /// Catch a panicking action with `std::panic::catch_unwind`, returning a
+    /// `Panicked` error with the panic message instead of unwinding. Off by default.
+    ///
+    /// Actions are not required to be `UnwindSafe`, they are wrapped in
+    /// `AssertUnwindSafe`: state an action shares through an `Arc` may be left
+    /// half updated by the panic, and a `Mutex` it held poisoned. The panic hook
+    /// still runs, printing the message to stderr unless replaced with
+    /// `std::panic::set_hook`.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::error::{ActionError, ActionErrorKind};
+    /// use suihorse::{App, Command};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build").action(|_| panic!("out of disk")))
+    ///     .catch_unwind(true);
+    ///
+    /// let args = vec!["cli", "build"];
+    /// let error = app.run_with_result(args.into_iter().map(String::from).collect()).unwrap_err();
+    /// assert_eq!(
+    ///     error.downcast_ref::<ActionError>().unwrap().kind,
+    ///     ActionErrorKind::Panicked("out of disk".to_string())
+    /// );
+    /// ```
+    pub fn catch_unwind(mut self, catch_unwind: bool) -> Self {
+        self.catch_unwind = catch_unwind;
+        self
     }
-}
\ No newline at end of file
+
+    /// Expand an `@path` arg before `--` to the whitespace separated args read
+    /// from the file at `path`, which may reference other response files.
+    /// A file that can't be read returns a `ResponseFile` error. Off by default.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use std::{env, fs};
+    /// use suihorse::{App, Flag, FlagType};
+    ///
+    /// let path = env::temp_dir().join("suihorse_response_file_args.txt");
+    /// fs::write(&path, "--verbose build\n").unwrap();
+    ///
+    /// let app = App::new("cli")
+    ///     .flag(Flag::new("verbose", FlagType::Bool))
+    ///     .action(|c| {
+    ///         assert!(c.bool_flag("verbose"));
+    ///         assert_eq!(c.args, vec!["build", "@kept"]);
+    ///     })
+    ///     .response_files(true);
+    ///
+    /// let args = vec!["cli".to_string(), format!("@{}", path.display()), "--".to_string(), "@kept".to_string()];
+    /// app.run_with_result(args).unwrap();
+    ///
+    /// let args = vec!["cli".to_string(), "@missing_response_file.txt".to_string()];
+    /// let error = app.run_with_result(args).unwrap_err();
+    /// assert!(error.to_string().starts_with("ResponseFile: @missing_response_file.txt: "));
+    /// fs::remove_file(path).unwrap();
+    /// ```
+    pub fn response_files(mut self, response_files: bool) -> Self {
+        self.response_files = response_files;
+        self
+    }
+
+    /// Return a `NoCommand` error, after printing the help to stderr, when no command
+    /// matches and the app has no action. Off by default, showing the help on stdout
+    /// is then `Ok`.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::error::{ActionError, ActionErrorKind};
+    /// use suihorse::{App, Command, Outcome};
+    ///
+    /// let app = App::new("cli").command(Command::new("build").action(|_| {}));
+    ///
+    /// let args = vec!["cli"];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert_eq!(invocation.outcome, Outcome::HelpShown);
+    ///
+    /// let app = app.no_command_error(true);
+    /// let error = app.run_slice(&["cli"]).unwrap_err();
+    /// assert_eq!(
+    ///     error.downcast_ref::<ActionError>().unwrap().kind,
+    ///     ActionErrorKind::NoCommand
+    /// );
+    /// assert_eq!(error.to_string(), "NoCommand: no command specified");
+    ///
+    /// // an explicit help request is not an error
+    /// assert!(app.run_slice(&["cli", "--help"]).is_ok());
+    /// ```
+    pub fn no_command_error(mut self, no_command_error: bool) -> Self {
+        self.no_command_error = no_command_error;
+        self
+    }
+
+    /// Show help instead of running the app action when the app is run
+    /// without any arg. On by default, turn it off to run the action with
+    /// empty args. Apps without action always show help, see `no_command_error`.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Outcome};
+    ///
+    /// let app = App::new("cli").action(|_| unreachable!());
+    /// let invocation = app.run_logged(vec!["cli".to_string()]).unwrap();
+    /// assert_eq!(invocation.outcome, Outcome::HelpShown);
+    ///
+    /// let app = App::new("cli")
+    ///     .action(|c| assert!(c.args.is_empty()))
+    ///     .no_args_is_help(false);
+    /// let invocation = app.run_logged(vec!["cli".to_string()]).unwrap();
+    /// assert_eq!(invocation.outcome, Outcome::ActionRan);
+    /// ```
+    pub fn no_args_is_help(mut self, no_args_is_help: bool) -> Self {
+        self.no_args_is_help = no_args_is_help;
+        self
+    }
+
+    /// Run the command named `name` with every arg when the first one matches
+    /// no command, instead of the app action, so its flags and help are reused.
+    /// A matching command still takes precedence, and help flags and
+    /// `no_args_is_help` still show the app help.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Flag, FlagType};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(
+    ///         Command::new("run")
+    ///             .flag(Flag::new("release", FlagType::Bool))
+    ///             .action(|c| {
+    ///                 assert!(c.bool_flag("release"));
+    ///                 assert_eq!(c.args, vec!["foo"]);
+    ///             }),
+    ///     )
+    ///     .command(Command::new("build").action(|c| assert_eq!(c.args, vec!["foo"])))
+    ///     .default_command("run");
+    ///
+    /// let args = vec!["cargo", "foo", "--release"];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert_eq!(invocation.command_path, vec!["run"]);
+    ///
+    /// let args = vec!["cargo", "build", "foo"];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert_eq!(invocation.command_path, vec!["build"]);
+    /// ```
+    pub fn default_command<T: Into<String>>(mut self, name: T) -> Self {
+        self.default_command = Some(name.into());
+        self
+    }
+
+    /// Return a `NoAction` error when positional args are given to a command without
+    /// action and matching no sub command, instead of printing its help. Off by default.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::error::{ActionError, ActionErrorKind};
+    /// use suihorse::{App, Command, Outcome};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("remote").command(Command::new("add").action(|_| {})))
+    ///     .no_action_error(true);
+    ///
+    /// let error = app.run_slice(&["cli", "remote", "origin"]).unwrap_err();
+    /// assert_eq!(
+    ///     error.downcast_ref::<ActionError>().unwrap().kind,
+    ///     ActionErrorKind::NoAction("remote".to_string())
+    /// );
+    ///
+    /// let args = vec!["cli", "remote"];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert_eq!(invocation.outcome, Outcome::HelpShown);
+    /// ```
+    pub fn no_action_error(mut self, no_action_error: bool) -> Self {
+        self.no_action_error = no_action_error;
+        self
+    }
+
+    /// Accept a unique prefix of a long flag for that flag, such as `--verb` for
+    /// `--verbose`, returning an `AmbiguousFlag` error with the candidates when the
+    /// prefix is shared. Short `-x` flags are never abbreviated. Off by default.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::error::{ActionError, ActionErrorKind};
+    /// use suihorse::{App, Command, Flag, FlagType};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build")
+    ///         .flag(Flag::new("verbose", FlagType::Bool))
+    ///         .flag(Flag::new("version", FlagType::String))
+    ///         .flag(Flag::new("ver", FlagType::Bool))
+    ///         .action(|c| {
+    ///             assert!(c.bool_flag("verbose") && c.bool_flag("ver"));
+    ///             assert_eq!(c.flag_raw("version"), Some("2"));
+    ///         }))
+    ///     .abbreviate_flags(true);
+    ///
+    /// let args = vec!["cli", "build", "--verb", "--vers=2", "--ver"];
+    /// app.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    ///
+    /// let args = vec!["cli", "build", "--ve"];
+    /// let error = app.run_with_result(args.into_iter().map(String::from).collect()).unwrap_err();
+    /// assert_eq!(
+    ///     error.downcast_ref::<ActionError>().unwrap().kind,
+    ///     ActionErrorKind::AmbiguousFlag(
+    ///         "--ve".to_string(),
+    ///         vec!["--verbose".to_string(), "--version".to_string(), "--ver".to_string()]
+    ///     )
+    /// );
+    /// ```
+    pub fn abbreviate_flags(mut self, abbreviate_flags: bool) -> Self {
+        self.abbreviate_flags = abbreviate_flags;
+        self
+    }
+
+    /// Accept Windows style flags, `/name` for `--name` and `/name:value` for
+    /// `--name value`, where `name` is the name or an alias of a declared flag.
+    /// Other args starting with `/`, such as paths, stay positional. Off by default.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Flag, FlagType};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("cat")
+    ///         .flag(Flag::new("verbose", FlagType::Bool).alias("v"))
+    ///         .flag(Flag::new("output", FlagType::String))
+    ///         .action(|c| {
+    ///             assert!(c.bool_flag("verbose"));
+    ///             assert_eq!(c.flag_raw("output"), Some("out.txt"));
+    ///             assert_eq!(c.args, vec!["/etc/passwd"]);
+    ///         }))
+    ///     .windows_flags(true);
+    ///
+    /// let args = vec!["cli", "cat", "/v", "/output:out.txt", "/etc/passwd"];
+    /// app.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// ```
+    pub fn windows_flags(mut self, windows_flags: bool) -> Self {
+        self.windows_flags = windows_flags;
+        self
+    }
+
+    /// Return an `UnknownFlags` error listing every `--flag` given before `--`
+    /// that matches no declared flag, instead of keeping them in `Context::args`.
+    /// Off by default.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::error::{ActionError, ActionErrorKind};
+    /// use suihorse::{App, Command, Flag, FlagType};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build")
+    ///         .flag(Flag::new("release", FlagType::Bool))
+    ///         .action(|c| assert_eq!(c.args, vec!["--target"])))
+    ///     .deny_unknown_flags(true);
+    ///
+    /// let error = app
+    ///     .run_slice(&["cli", "build", "--release", "--jobs=4", "--verbose"])
+    ///     .unwrap_err();
+    /// assert_eq!(
+    ///     error.downcast_ref::<ActionError>().unwrap().kind,
+    ///     ActionErrorKind::UnknownFlags(vec!["--jobs".to_string(), "--verbose".to_string()])
+    /// );
+    /// assert_eq!(error.to_string(), "UnknownFlags: --jobs, --verbose");
+    ///
+    /// // args after `--` are never flags
+    /// app.run_slice(&["cli", "build", "--release", "--", "--target"]).unwrap();
+    /// ```
+    pub fn deny_unknown_flags(mut self, deny_unknown_flags: bool) -> Self {
+        self.deny_unknown_flags = deny_unknown_flags;
+        self
+    }
+
+    /// Run the command whose name or alias is the binary name in `argv[0]`, such as
+    /// a `build` symlink to the binary, with every following arg. Off by default,
+    /// ignoring `argv[0]`.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build").action(|c| assert_eq!(c.args, vec!["--release"])))
+    ///     .command(Command::new("test").action(|_| unreachable!()))
+    ///     .dispatch_by_program_name(true);
+    ///
+    /// let args = vec!["/usr/local/bin/build", "--release"];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert_eq!(invocation.command_path, vec!["build"]);
+    /// ```
+    pub fn dispatch_by_program_name(mut self, dispatch_by_program_name: bool) -> Self {
+        self.dispatch_by_program_name = dispatch_by_program_name;
+        self
+    }
+
+    /// Set exit code returned by `run_and_exit_code` when the run fails,
+    /// an `ExitCode` error returned by an action takes precedence
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::App;
+    ///
+    /// let app = App::new("cli")
+    ///     .strict(true)
+    ///     .error_exit_code(2);
+    ///
+    /// let args = vec!["cli", "deploy"];
+    /// assert_eq!(app.run_and_exit_code(args.into_iter().map(String::from).collect()), 2);
+    /// ```
+    pub fn error_exit_code(mut self, error_exit_code: i32) -> Self {
+        self.error_exit_code = error_exit_code;
+        self
+    }
+
+    /// Set bool flag parsed by every command and the app action, such as `--dry-run`,
+    /// listed in the app help and in every command help.
+    /// It may also be given before the command name.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    /// use suihorse::{App, Command, Help, Outcome};
+    ///
+    /// let dry_run = Arc::new(AtomicBool::new(false));
+    /// let seen = dry_run.clone();
+    /// let add = Command::new("add")
+    ///     .action(move |c| seen.store(c.bool_flag("dry-run"), Ordering::SeqCst));
+    /// let app = App::new("cli")
+    ///     .command(Command::new("remote").command(add))
+    ///     .global_bool_flag("dry-run");
+    ///
+    /// for args in [
+    ///     vec!["cli", "remote", "add", "origin", "--dry-run"],
+    ///     vec!["cli", "--dry-run", "remote", "add", "origin"],
+    /// ] {
+    ///     dry_run.store(false, Ordering::SeqCst);
+    ///     let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    ///     assert_eq!(invocation.outcome, Outcome::ActionRan);
+    ///     assert_eq!(invocation.command_path, vec!["remote", "add"]);
+    ///     assert!(dry_run.load(Ordering::SeqCst));
+    /// }
+    ///
+    /// assert!(app.help_text().contains("--dry-run"));
+    /// assert!(app.command_help_text("cli", &["remote", "add"]).unwrap().contains("--dry-run"));
+    /// ```
+    pub fn global_bool_flag<T: Into<String>>(self, name: T) -> Self {
+        self.global_flag(Flag::new(name, FlagType::Bool))
+    }
+
+    /// Set flag parsed by every command and the app action, such as `--config <path>`,
+    /// listed in the app help and in every command help.
+    /// It may also be given before the command name, with its value.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use suihorse::{App, Command, Flag, FlagType, Outcome};
+    ///
+    /// let resolved = Arc::new(Mutex::new(None));
+    /// let seen = resolved.clone();
+    /// let app = App::new("cli")
+    ///     .command(Command::new("remote").command(Command::new("add").action(move |c| {
+    ///         let config = c.flag_raw("config").map(String::from);
+    ///         *seen.lock().unwrap() = Some((config, c.bool_flag("verbose"), c.args.clone()));
+    ///     })))
+    ///     .global_flag(Flag::new("config", FlagType::String).short('c'))
+    ///     .global_flag(Flag::new("verbose", FlagType::Bool));
+    ///
+    /// for args in [
+    ///     vec!["cli", "remote", "add", "origin", "--config", "ci.toml", "--verbose"],
+    ///     vec!["cli", "-c", "ci.toml", "--verbose", "remote", "add", "origin"],
+    ///     vec!["cli", "--config=ci.toml", "remote", "add", "--verbose", "origin"],
+    /// ] {
+    ///     let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    ///     assert_eq!(invocation.outcome, Outcome::ActionRan);
+    ///     assert_eq!(invocation.command_path, vec!["remote", "add"]);
+    ///     assert_eq!(
+    ///         resolved.lock().unwrap().take(),
+    ///         Some((Some("ci.toml".to_string()), true, vec!["origin".to_string()]))
+    ///     );
+    /// }
+    /// ```
+    pub fn global_flag(mut self, flag: Flag) -> Self {
+        if let Some(ref mut flags) = self.global_flags {
+            (*flags).push(flag);
+        } else {
+            self.global_flags = Some(vec![flag]);
+        }
+        self
+    }
+
+    /// Set flag of the app
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Flag, FlagType};
+    ///
+    /// let app = App::new("cli")
+    ///     .flag(Flag::new("verbose", FlagType::Bool).alias("v"));
+    /// ```
+    pub fn flag(mut self, flag: Flag) -> Self {
+        if let Some(ref mut flags) = self.flags {
+            (*flags).push(flag);
+        } else {
+            self.flags = Some(vec![flag]);
+        }
+        self
+    }
+
+    /// Accept a global `--config-dump` flag that prints every resolved flag
+    /// with its value and source, then exits without running any action
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::App;
+    ///
+    /// let app = App::new("cli")
+    ///     .config_dump(true);
+    /// ```
+    pub fn config_dump(mut self, config_dump: bool) -> Self {
+        self.config_dump = config_dump;
+        self
+    }
+
+    /// Set a `KEY=VALUE` file read before parsing, its variables are used by flag
+    /// env fallbacks when not set in the process env, so the command line and the
+    /// real env take precedence. The process env is left untouched.
+    /// Blank lines, `#` comments and quoted values are handled.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use suihorse::{App, Command, Flag, FlagType};
+    ///
+    /// let path = std::env::temp_dir().join("suihorse_env_file_example.env");
+    /// std::fs::write(&path, "# deployment\nMYAPP_REGION=\"eu-west\"\n").unwrap();
+    /// std::env::remove_var("MYAPP_REGION");
+    ///
+    /// let region = Arc::new(Mutex::new(None));
+    /// let seen = region.clone();
+    /// let command = Command::new("deploy")
+    ///     .flag(Flag::new("region", FlagType::String).env("MYAPP_REGION"))
+    ///     .action(move |c| *seen.lock().unwrap() = c.flag_raw("region").map(String::from));
+    /// let app = App::new("cli")
+    ///     .command(command)
+    ///     .env_file(path.to_str().unwrap());
+    ///
+    /// let args: Vec<String> = vec!["myapp".into(), "deploy".into()];
+    /// app.run_with_result(args.clone()).unwrap();
+    /// assert_eq!(region.lock().unwrap().as_deref(), Some("eu-west"));
+    /// assert!(std::env::var("MYAPP_REGION").is_err());
+    /// assert!(app.config_dump_text(args).contains("--region=eu-west (env)"));
+    /// ```
+    pub fn env_file<T: Into<String>>(mut self, path: T) -> Self {
+        self.env_file = Some(path.into());
+        self
+    }
+
+    /// Read every flag without a value on the command line from the env variable
+    /// named after it under `prefix`, such as `MYAPP_LOG_LEVEL` for `--log-level`.
+    /// A variable set with `Flag::env` is read first, the default value last.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Flag, FlagType};
+    ///
+    /// std::env::set_var("MYAPP_LOG_LEVEL", "debug");
+    /// std::env::set_var("MYAPP_JOBS", "8");
+    /// std::env::set_var("BUILD_JOBS", "2");
+    ///
+    /// let command = Command::new("build")
+    ///     .flag(Flag::new("log-level", FlagType::String).default_value("info"))
+    ///     .flag(Flag::new("jobs", FlagType::Int).env("BUILD_JOBS"))
+    ///     .flag(Flag::new("release", FlagType::Bool))
+    ///     .action(|c| {
+    ///         assert_eq!(c.string_flag("log-level").unwrap(), "debug");
+    ///         assert_eq!(c.int_flag("jobs").unwrap(), 2);
+    ///         assert!(c.env_sourced_flags().contains(&"log-level"));
+    ///     });
+    /// let app = App::new("cli")
+    ///     .command(command)
+    ///     .env_prefix("MYAPP");
+    ///
+    /// app.run_slice(&["myapp", "build"]).unwrap();
+    /// ```
+    pub fn env_prefix<T: Into<String>>(mut self, prefix: T) -> Self {
+        self.env_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Set a file of flag values, read before parsing when it exists, a
+    /// leading `~/` standing for the home directory. Top level keys are flag
+    /// names, keys under a `[command]` table only apply to that command and take
+    /// precedence, `[remote.add]` for nested ones. The command line and env take
+    /// precedence over the file, which takes precedence over default values.
+    /// `Context::flag_source` tells which one a value came from.
+    /// Requires the `config-file` feature.
+    ///
+    /// The file is TOML, tables being written as `[build]` headers, dotted
+    /// keys such as `build.jobs = 8` or inline tables. Values are strings,
+    /// integers, floats and booleans, arrays and dates fail the run with a
+    /// `ConfigFile` error naming the key, and invalid TOML with one naming
+    /// the line.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Flag, FlagSource, FlagType};
+    ///
+    /// let path = std::env::temp_dir().join("suihorse_config_file_example.toml");
+    /// std::fs::write(&path, concat!(
+    ///     "network = \"testnet\"\n",
+    ///     "jobs = 2\n",
+    ///     "\n",
+    ///     "[build]\n",
+    ///     "jobs = 8 # all cores\n",
+    ///     "release = true\n",
+    /// )).unwrap();
+    ///
+    /// let command = Command::new("build")
+    ///     .flag(Flag::new("jobs", FlagType::Int).default_value("1"))
+    ///     .flag(Flag::new("release", FlagType::Bool))
+    ///     .flag(Flag::new("target", FlagType::String).default_value("native"))
+    ///     .action(|c| {
+    ///         assert_eq!(c.int_flag("jobs").unwrap(), 8);
+    ///         assert!(c.bool_flag("release"));
+    ///         assert_eq!(c.string_flag("network").unwrap(), "mainnet");
+    ///         assert_eq!(c.flag_source("jobs"), Some(FlagSource::Config));
+    ///         assert_eq!(c.flag_source("network"), Some(FlagSource::Cli));
+    ///         assert_eq!(c.flag_source("target"), Some(FlagSource::Default));
+    ///     });
+    /// let app = App::new("cli")
+    ///     .global_flag(Flag::new("network", FlagType::String))
+    ///     .command(command)
+    ///     .config_file(path.to_str().unwrap());
+    ///
+    /// app.run_slice(&["myapp", "build", "--network", "mainnet"]).unwrap();
+    ///
+    /// // a value that is not a bool fails the run
+    /// std::fs::write(&path, "build.release = \"maybe\"\n").unwrap();
+    /// let error = app.run_slice(&["myapp", "build", "--network", "mainnet"]).unwrap_err();
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     r#"Parse: invalid value "maybe" for flag --release (expected bool)"#
+    /// );
+    /// ```
+    ///
+    /// An unsupported value or a malformed file fails the run with a
+    /// `ConfigFile` error
+    ///
+    /// ```
+    /// use suihorse::App;
+    ///
+    /// let path = std::env::temp_dir().join("suihorse_config_file_error.toml");
+    /// let app = App::new("cli").config_file(path.to_str().unwrap()).action(|_| {});
+    ///
+    /// std::fs::write(&path, "[build]\njobs = [1, 2]\n").unwrap();
+    /// let error = app.run_slice(&["myapp"]).unwrap_err();
+    /// assert!(error.to_string().ends_with("unsupported value `[1, 2]` for key `build.jobs`"));
+    ///
+    /// std::fs::write(&path, "jobs = 2\njobs = 3\n").unwrap();
+    /// let error = app.run_slice(&["myapp"]).unwrap_err();
+    /// assert!(error.to_string().ends_with("line 2: duplicate key"));
+    /// ```
+    #[cfg(feature = "config-file")]
+    pub fn config_file<T: Into<String>>(mut self, path: T) -> Self {
+        self.config_file = Some(path.into());
+        self
+    }
+
+    /// Read `.env` in the working directory before parsing, as `env_file` does,
+    /// when it exists. An `env_file` path is read in its place.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicI64, Ordering};
+    /// use std::sync::Arc;
+    /// use suihorse::{App, Command, Flag, FlagType};
+    ///
+    /// let dir = std::env::temp_dir().join("suihorse_dotenv_example");
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// std::fs::write(dir.join(".env"), "MYAPP_PORT=3000\n").unwrap();
+    /// std::env::set_current_dir(&dir).unwrap();
+    /// std::env::remove_var("MYAPP_PORT");
+    ///
+    /// let port = Arc::new(AtomicI64::new(0));
+    /// let seen = port.clone();
+    /// let command = Command::new("serve")
+    ///     .flag(Flag::new("port", FlagType::Int).env("MYAPP_PORT").default_value("8080"))
+    ///     .action(move |c| seen.store(c.int_flag("port").unwrap(), Ordering::SeqCst));
+    /// let app = App::new("cli")
+    ///     .command(command)
+    ///     .dotenv(true);
+    /// app.run_slice(&["myapp", "serve"]).unwrap();
+    /// assert_eq!(port.load(Ordering::SeqCst), 3000);
+    ///
+    /// // a custom path instead of `.env`
+    /// let path = dir.join("staging.env");
+    /// std::fs::write(&path, "MYAPP_PORT=4000\n").unwrap();
+    ///
+    /// let app = app.env_file(path.to_str().unwrap());
+    /// app.run_slice(&["myapp", "serve"]).unwrap();
+    /// assert_eq!(port.load(Ordering::SeqCst), 4000);
+    /// ```
+    pub fn dotenv(mut self, dotenv: bool) -> Self {
+        self.dotenv = dotenv;
+        self
+    }
+
+    /// Set env variable silencing the warning printed when a `Command::deprecated`
+    /// command runs, when set to a non empty value
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("pull").deprecated("use `sync` instead").action(|_| {}))
+    ///     .no_deprecation_warnings_env("MYAPP_NO_DEPRECATION_WARNINGS");
+    ///
+    /// std::env::set_var("MYAPP_NO_DEPRECATION_WARNINGS", "1");
+    /// // runs without printing the warning
+    /// assert_eq!(app.deprecation_warning_text(&["pull"]), None);
+    /// app.run_slice(&["cli", "pull"]).unwrap();
+    /// ```
+    pub fn no_deprecation_warnings_env<T: Into<String>>(mut self, name: T) -> Self {
+        self.no_deprecation_warnings_env = Some(name.into());
+        self
+    }
+
+    /// Text printed by `--config-dump` for `args`, listing the app flags,
+    /// the flags of the command matched down the nested commands, as `dispatch`
+    /// does, then the global flags
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Flag, FlagType};
+    ///
+    /// std::env::set_var("MYAPP_TOKEN", "secret");
+    ///
+    /// let command = Command::new("build")
+    ///     .flag(Flag::new("release", FlagType::Bool))
+    ///     .flag(Flag::new("token", FlagType::String).env("MYAPP_TOKEN"))
+    ///     .flag(Flag::new("jobs", FlagType::Int).default_value("4"));
+    /// let app = App::new("cli")
+    ///     .command(command)
+    ///     .config_dump(true);
+    ///
+    /// let args = vec!["myapp", "build", "--release", "--config-dump"];
+    /// let text = app.config_dump_text(args.into_iter().map(String::from).collect());
+    /// assert!(text.contains("--release=true (cli)"));
+    /// assert!(text.contains("--token=secret (env)"));
+    /// assert!(text.contains("--jobs=4 (default)"));
+    /// ```
+    ///
+    /// Nested commands and global flags given before them are resolved too
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Flag, FlagType};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("remote").command(
+    ///         Command::new("add").flag(Flag::new("force", FlagType::Bool)),
+    ///     ))
+    ///     .global_flag(Flag::new("profile", FlagType::String).default_value("dev"))
+    ///     .config_dump(true);
+    ///
+    /// let args = vec!["myapp", "remote", "add", "--force", "--config-dump"];
+    /// let text = app.config_dump_text(args.into_iter().map(String::from).collect());
+    /// assert_eq!(text, "Config:\n\t--force=true (cli)\n\t--profile=dev (default)\n");
+    ///
+    /// let args = vec!["myapp", "--profile", "ci", "remote", "add", "--config-dump"];
+    /// let text = app.config_dump_text(args.into_iter().map(String::from).collect());
+    /// assert_eq!(text, "Config:\n\t--profile=ci (cli)\n");
+    /// ```
+    pub fn config_dump_text(&self, args: Vec<String>) -> String {
+        let env_vars = self.env_file_values().unwrap_or_default();
+        let config = self.config_values().unwrap_or_default();
+        let mut dispatch = self.dispatch_settings("");
+        dispatch.env_vars = &env_vars;
+        dispatch.config = &config;
+
+        // walk the nested commands, removing their names from the args
+        let mut args_v = args.get(1..).unwrap_or(&[]).to_vec();
+        let mut command_path = vec![];
+        let mut command_flags: &[Flag] = &[];
+        let leading = dispatch.leading_global_flags(&args_v);
+        let mut found = self.find_command(&args_v[leading..]).ok().flatten();
+        while let Some(command) = found {
+            args_v.remove(dispatch.leading_global_flags(&args_v));
+            command_path.push(command.name.clone());
+            command_flags = command.flags.as_deref().unwrap_or(&[]);
+            let leading = dispatch.leading_global_flags(&args_v);
+            found = command.find_command(&dispatch, &args_v[leading..]);
+            dispatch.parents.push(command.name.clone());
+        }
+
+        let app_flags = self.flags.iter().flatten();
+        let mut flags: Vec<(&Flag, &[String])> = app_flags.map(|f| (f, &[][..])).collect();
+        flags.extend(command_flags.iter().map(|f| (f, command_path.as_slice())));
+        flags.extend(dispatch.global_flags.iter().map(|f| (f, command_path.as_slice())));
+
+        let declared: Vec<&Flag> = flags.iter().map(|(f, _)| *f).collect();
+        let flag_args = expanded_short_flags(flag_args(&args_v, &declared), &declared);
+        let flag_args = bool_assignments(&flag_args, &declared).unwrap_or(flag_args);
+
+        let mut text = String::new();
+
+        text += "Config:\n";
+
+        for (f, path) in flags {
+            if let Some((value, source)) = f.resolve(&flag_args, &dispatch, path) {
+                text += &format!("\t{}={} ({})\n", f.token(), value, source);
+            }
+        }
+
+        text
+    }
+
+    /// Usage shown in the app help: the explicit usage, else the one synthesized
+    /// from the app name, its commands, flags and global flags, ending with
+    /// `[arg]...` when the app has an action
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Flag, FlagType, Help};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build"))
+    ///     .flag(Flag::new("jobs", FlagType::Int))
+    ///     .global_bool_flag("verbose")
+    ///     .action(|c| println!("{:?}", c.args));
+    /// assert_eq!(app.usage_text(), "cli [command] [--jobs <int>] [--verbose] [arg]...");
+    /// assert!(app.help_text().contains(&app.usage_text()));
+    ///
+    /// let app = app.usage("cli [command] [arg]");
+    /// assert_eq!(app.usage_text(), "cli [command] [arg]");
+    /// ```
+    pub fn usage_text(&self) -> String {
+        match &self.usage {
+            Some(usage) => usage.to_owned(),
+            None => {
+                let flags: Vec<&Flag> = self
+                    .flags
+                    .iter()
+                    .chain(self.global_flags.iter())
+                    .flatten()
+                    .collect();
+                generated_app_usage(
+                    &self.name,
+                    !self.commands.is_empty(),
+                    &flags,
+                    self.action.is_some(),
+                )
+            }
+        }
+    }
+
+    /// Names of the app commands in registration order
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build"))
+    ///     .command(Command::new("test"));
+    ///
+    /// assert_eq!(app.command_names(), vec!["build", "test"]);
+    /// ```
+    pub fn command_names(&self) -> Vec<&str> {
+        self.commands.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    /// Usage shown in the help of the command `name` for the app installed as
+    /// `bin_name`, None without an explicit usage when `auto_usage` is off
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Flag, FlagType};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build").flag(Flag::new("jobs", FlagType::Int)));
+    /// assert_eq!(app.command_usage("myapp", "build").unwrap(), "myapp build [--jobs <int>]");
+    ///
+    /// let app = app.auto_usage(false);
+    /// assert!(app.command_usage("myapp", "build").is_none());
+    /// ```
+    pub fn command_usage(&self, bin_name: &str, name: &str) -> Option<String> {
+        let command = self.commands.iter().find(|c| c.name == name)?;
+        command.shown_usage(bin_name, &[], self.auto_usage)
+    }
+
+    /// Help text shown for the command at `path`, a list of command names from
+    /// the top, for the app installed as `bin_name`. Its usage includes
+    /// the parent command names.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("remote")
+    ///         .command(Command::new("branch")
+    ///             .command(Command::new("add").usage("add <name> <url>"))));
+    ///
+    /// let text = app.command_help_text("myapp", &["remote", "branch", "add"]).unwrap();
+    /// assert!(text.contains("Usage:\n\tmyapp remote branch add <name> <url>\n"));
+    /// assert!(app.command_help_text("myapp", &["remote", "tag"]).is_none());
+    /// ```
+    pub fn command_help_text(&self, bin_name: &str, path: &[&str]) -> Option<String> {
+        let (name, sub_path) = path.split_first()?;
+        let mut command = self.commands.iter().find(|c| c.name == *name)?;
+        let mut parents = vec![];
+        for name in sub_path {
+            parents.push(command.name.clone());
+            command = command.commands.iter().flatten().find(|c| c.name == *name)?;
+        }
+
+        let mut dispatch = self.dispatch_settings(bin_name);
+        dispatch.parents = parents;
+        Some(command.dispatched_help_text(&dispatch))
+    }
+
+    /// Generate a bash completion script for the app installed as `bin_name`
+    ///
+    /// The words typed so far select the command path, nested commands
+    /// included, whose sub commands and flags are completed with the global flags.
+    /// Flags are completed by their name, all of their aliases, even the ones
+    /// not listed in help, and the `--no-` form of bool flags.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("remote")
+    ///     .alias("r")
+    ///     .command(Command::new("add").flag(Flag::new("force", FlagType::Bool).alias("f")));
+    /// let app = App::new("cli")
+    ///     .command(command)
+    ///     .flag(Flag::new("color", FlagType::Bool).alias("colour"))
+    ///     .global_flag(Flag::new("profile", FlagType::String));
+    ///
+    /// let script = app.bash_completion("cli");
+    /// assert!(script.contains(r#"":remote"|":r") path="remote" ;;"#));
+    /// assert!(script.contains(r#""remote:add") path="remote add" ;;"#));
+    /// assert!(script.contains(r#"words="add --profile -h --help""#));
+    /// assert!(script.contains(r#"words="--force -f --no-force --profile -h --help""#));
+    /// assert!(script.contains(r#"words="remote r --color --colour --no-color --profile -h --help""#));
+    /// assert!(script.contains("complete -F _cli cli"));
+    /// ```
+    pub fn bash_completion<T: Into<String>>(&self, bin_name: T) -> String {
+        completion::bash(self, &bin_name.into())
+    }
+
+    /// Describe the command tree as JSON for documentation tools: the fields of
+    /// the app, and of each flag and command down the sub commands, with
+    /// `Serialize`. Actions and validators are left out.
+    /// Requires the `serde` feature.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Flag, FlagType};
+    ///
+    /// let app = App::new("cli")
+    ///     .description("file sync")
+    ///     .command(
+    ///         Command::new("remote")
+    ///             .alias("r")
+    ///             .description("manage remotes")
+    ///             .command(Command::new("add").flag(Flag::new("force", FlagType::Bool))),
+    ///     );
+    ///
+    /// let json = app.to_json();
+    /// assert!(json.starts_with(r#"{"name":"cli","author":null,"version":null,"description":"file sync","#));
+    /// assert!(json.contains(r#""name":"remote","alias":["r"],"description":"manage remotes""#));
+    /// assert!(json.contains(r#""name":"force","description":null,"flag_type":"bool""#));
+    /// assert!(!json.contains(r#""action""#));
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        // strings, bools, numbers and lists of them never fail to serialize
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Run app, exiting the process with the code of an `ExitCode` error
+    /// returned by an action and panicking on any other error
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use std::env;
+    /// use suihorse::App;
+    ///
+    /// let args: Vec<String> = env::args().collect();
+    /// let app = App::new("cli");
+    /// app.run(args);
+    /// ```
+    pub fn run(&self, args: Vec<String>) {
+        if let Err(e) = self.run_with_result(args) {
+            match e.downcast_ref::<ExitCode>() {
+                Some(ExitCode(code)) => process::exit(*code),
+                None => panic!("{}", e),
+            }
+        }
+    }
+
+    /// Run app, returning the process exit code: 0 when an action ran or help,
+    /// version or config was shown on request, the code of an `ExitCode` error
+    /// returned by the action, or `error_exit_code` when the run otherwise
+    /// failed, including help printed because no command matched in strict mode
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build").action(|_| println!("build")))
+    ///     .strict(true);
+    ///
+    /// let args = vec!["cli", "build", "--help"];
+    /// assert_eq!(app.run_and_exit_code(args.into_iter().map(String::from).collect()), 0);
+    ///
+    /// let args = vec!["cli", "deploy"];
+    /// assert_eq!(app.run_and_exit_code(args.into_iter().map(String::from).collect()), 1);
+    /// ```
+    pub fn run_and_exit_code(&self, args: Vec<String>) -> i32 {
+        match self.run_with_result(args) {
+            Ok(()) => 0,
+            Err(e) => self.error_code(e.as_ref()),
+        }
+    }
+
+    /// Same as `run_and_exit_code`, printing the error of a failed run to stderr.
+    /// An `ExitCode` error is not printed, only its code is returned.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build").action(|_| println!("build")))
+    ///     .command(Command::new("deploy").action_with_result(|_| Err("no target".into())))
+    ///     .error_exit_code(2);
+    ///
+    /// assert_eq!(app.run_and_report(vec!["cli".to_string(), "build".to_string()]), 0);
+    /// // prints "Error: no target" to stderr
+    /// assert_eq!(app.run_and_report(vec!["cli".to_string(), "deploy".to_string()]), 2);
+    /// ```
+    pub fn run_and_report(&self, args: Vec<String>) -> i32 {
+        match self.run_with_result(args) {
+            Ok(()) => 0,
+            Err(e) => {
+                if !e.is::<ExitCode>() {
+                    eprintln!("Error: {}", e);
+                }
+                self.error_code(e.as_ref())
+            }
+        }
+    }
+
+    /// Exit code of a run failing with `error`
+    fn error_code(&self, error: &(dyn Error + 'static)) -> i32 {
+        match error.downcast_ref::<ExitCode>() {
+            Some(ExitCode(code)) => *code,
+            None => self.error_exit_code,
+        }
+    }
+
+    /// Run app, returning a result
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use std::env;
+    /// use suihorse::App;
+    ///
+    /// let args: Vec<String> = env::args().collect();
+    /// let app = App::new("cli");
+    /// let result = app.run_with_result(args);
+    /// ```
+    ///
+    /// Everything after a standalone `--` is passed to the action verbatim
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let command = Command::new("run")
+    ///     .action(|c| assert_eq!(c.args, vec!["--not-a-flag", "value", "--foo=bar"]));
+    /// let app = App::new("cli")
+    ///     .command(command)
+    ///     .action(|_| unreachable!());
+    ///
+    /// let args = vec!["myapp", "run", "--", "--not-a-flag", "value", "--foo=bar"];
+    /// app.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// ```
+    ///
+    /// Flags are never dispatched as commands, `--build` is an unknown flag
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let command = Command::new("build")
+    ///     .action(|_| unreachable!());
+    /// let app = App::new("cli")
+    ///     .command(command)
+    ///     .action(|c| assert_eq!(c.args, vec!["--build"]));
+    ///
+    /// let args = vec!["myapp", "--build"];
+    /// app.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// ```
+    ///
+    /// Args are trimmed and empty ones are dropped before `--`,
+    /// so an empty token is never a command. The value of a flag is kept
+    /// as given, even when empty.
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Flag, FlagType, Outcome};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(
+    ///         Command::new("build")
+    ///             .flag(Flag::new("name", FlagType::String))
+    ///             .action(|c| {
+    ///                 assert_eq!(c.string_flag("name").unwrap(), "");
+    ///                 assert_eq!(c.args, vec!["out", "", " x "]);
+    ///             }),
+    ///     )
+    ///     .prefix_matching(true)
+    ///     .action(|_| unreachable!());
+    ///
+    /// let args = vec!["myapp", "", " build ", "--name", "", "  ", "out", "--", "", " x "];
+    /// app.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    ///
+    /// let args = vec!["myapp", ""];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert!(invocation.command_path.is_empty());
+    /// assert_eq!(invocation.outcome, Outcome::HelpShown);
+    /// ```
+    ///
+    /// The app action gets the same `Context` as command actions,
+    /// with flags parsed and the args after `--` kept verbatim
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Flag, FlagType};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build").action(|_| unreachable!()))
+    ///     .flag(Flag::new("verbose", FlagType::Bool))
+    ///     .action(|c| {
+    ///         assert!(c.bool_flag("verbose"));
+    ///         assert_eq!(c.args, vec!["file", "build", "--verbose", "-x=1"]);
+    ///     });
+    ///
+    /// let args = vec!["myapp", "--verbose", "file", "--", "build", "--verbose", "-x=1"];
+    /// app.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// ```
+    pub fn run_with_result(&self, args: Vec<String>) -> Result<(), Box<dyn Error>> {
+        self.run_logged(args).map(|_| ())
+    }
+
+    /// Run app like `run_with_result` with borrowed args, handy in tests
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Flag, FlagType};
+    ///
+    /// let app = App::new("cli").command(
+    ///     Command::new("build")
+    ///         .flag(Flag::new("verbose", FlagType::Bool))
+    ///         .action(|c| assert!(c.bool_flag("verbose"))),
+    /// );
+    ///
+    /// app.run_slice(&["prog", "build", "--verbose"]).unwrap();
+    /// ```
+    pub fn run_slice(&self, args: &[&str]) -> Result<(), Box<dyn Error>> {
+        self.run_with_result(args.iter().map(|arg| arg.to_string()).collect())
+    }
+
+    /// Run app, returning a summary of the invocation for audit logging
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Outcome};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build").action(|c| println!("{:?}", c.args)));
+    ///
+    /// let args = vec!["cli", "build", "--release"];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert_eq!(invocation.command_path, vec!["build"]);
+    /// assert_eq!(invocation.outcome, Outcome::ActionRan);
+    /// println!("took {:?}", invocation.duration);
+    /// ```
+    ///
+    /// Without an app action, no args or a token matching no command shows help,
+    /// to stderr for the token as `run_with_output` shows
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Outcome};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build").action(|_| unreachable!()));
+    ///
+    /// let args = vec!["cli", "deploy"];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert!(invocation.command_path.is_empty());
+    /// assert_eq!(invocation.outcome, Outcome::HelpShown);
+    ///
+    /// let args = vec!["cli"];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert_eq!(invocation.outcome, Outcome::HelpShown);
+    ///
+    /// let app = app.action(|c| assert!(c.args.is_empty())).no_args_is_help(false);
+    /// let args = vec!["cli"];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert_eq!(invocation.outcome, Outcome::ActionRan);
+    /// ```
+    pub fn run_logged(&self, args: Vec<String>) -> Result<Invocation, Box<dyn Error>> {
+        let start = Instant::now();
+        let (command_path, outcome) = self.dispatch(args)?;
+
+        Ok(Invocation {
+            command_path,
+            outcome,
+            duration: start.elapsed(),
+        })
+    }
+
+    /// Run app like `run_logged`, writing the help, version, config dump and
+    /// warnings it prints to `stdout` and `stderr` instead of the process
+    /// streams. Help asked for with a help flag goes to `stdout`, help shown
+    /// after a mistake, such as a token matching no command, to `stderr`.
+    /// What actions print is not captured.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Help, Outcome};
+    ///
+    /// let app = App::new("cli")
+    ///     .version("1.2.0")
+    ///     .command(Command::new("build").action(|_| {}))
+    ///     .command(Command::new("pull").deprecated("use `sync` instead").action(|_| {}));
+    /// let run = |args: &[&str]| {
+    ///     let (mut stdout, mut stderr) = (vec![], vec![]);
+    ///     let args = args.iter().map(|arg| arg.to_string()).collect();
+    ///     let outcome = app.run_with_output(args, &mut stdout, &mut stderr).unwrap().outcome;
+    ///     (outcome, String::from_utf8(stdout).unwrap(), String::from_utf8(stderr).unwrap())
+    /// };
+    ///
+    /// let help = format!("{}\n", app.help_text());
+    /// assert_eq!(run(&["cli", "--help"]), (Outcome::HelpShown, help.clone(), String::new()));
+    /// assert_eq!(run(&["cli", "deploy"]), (Outcome::HelpShown, String::new(), help));
+    /// let version = "cli 1.2.0\n".to_string();
+    /// assert_eq!(run(&["cli", "-V"]), (Outcome::VersionShown, version, String::new()));
+    /// assert_eq!(
+    ///     run(&["cli", "pull"]),
+    ///     (
+    ///         Outcome::ActionRan,
+    ///         String::new(),
+    ///         "Warning: command \"pull\" is deprecated, use `sync` instead\n".to_string()
+    ///     )
+    /// );
+    /// ```
+    ///
+    /// Sub commands route their help the same way, and in strict mode the
+    /// error for a token matching no command goes to `stderr`
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Help};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("remote").command(Command::new("add").action(|_| {})))
+    ///     .strict(true);
+    ///
+    /// let (mut stdout, mut stderr) = (vec![], vec![]);
+    /// let args = vec!["cli".to_string(), "remote".to_string(), "ad".to_string()];
+    /// app.run_with_output(args, &mut stdout, &mut stderr).unwrap();
+    /// assert!(stdout.is_empty());
+    /// assert!(String::from_utf8(stderr).unwrap().contains("add"));
+    ///
+    /// let (mut stdout, mut stderr) = (vec![], vec![]);
+    /// let args = vec!["cli".to_string(), "remote".to_string(), "-h".to_string()];
+    /// app.run_with_output(args, &mut stdout, &mut stderr).unwrap();
+    /// assert!(String::from_utf8(stdout).unwrap().contains("add"));
+    /// assert!(stderr.is_empty());
+    ///
+    /// let (mut stdout, mut stderr) = (vec![], vec![]);
+    /// let args = vec!["cli".to_string(), "deploy".to_string()];
+    /// assert!(app.run_with_output(args, &mut stdout, &mut stderr).is_err());
+    /// assert!(stdout.is_empty());
+    /// let error = format!("{}\n", app.not_found_text("deploy"));
+    /// assert_eq!(String::from_utf8(stderr).unwrap(), error);
+    /// ```
+    pub fn run_with_output(
+        &self,
+        args: Vec<String>,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+    ) -> Result<Invocation, Box<dyn Error>> {
+        let (stdout, stderr) = (RefCell::new(stdout), RefCell::new(stderr));
+        let print = |text: &str| {
+            let _ = writeln!(stdout.borrow_mut(), "{}", text);
+        };
+        let eprint = |text: &str| {
+            let _ = writeln!(stderr.borrow_mut(), "{}", text);
+        };
+        let output = Output {
+            stdout: Some(&print),
+            stderr: Some(&eprint),
+        };
+
+        let start = Instant::now();
+        let (command_path, outcome) = self.dispatch_to(args, output)?;
+
+        Ok(Invocation {
+            command_path,
+            outcome,
+            duration: start.elapsed(),
+        })
+    }
+
+    /// Settings threaded down the command tree for a run as `program_name`
+    fn dispatch_settings<'a>(&'a self, program_name: &'a str) -> Dispatch<'a> {
+        Dispatch {
+            program_name,
+            help_flags: &self.help_flags,
+            auto_usage: self.auto_usage,
+            catch_unwind: self.catch_unwind,
+            no_action_error: self.no_action_error,
+            abbreviate_flags: self.abbreviate_flags,
+            windows_flags: self.windows_flags,
+            deny_unknown_flags: self.deny_unknown_flags,
+            no_deprecation_warnings_env: self.no_deprecation_warnings_env.as_deref(),
+            global_flags: self.global_flags.as_deref().unwrap_or(&[]),
+            parents: vec![],
+            stdin: None,
+            version: self.version_text(),
+            env_prefix: self.env_prefix.as_deref(),
+            env_vars: &[],
+            config: &[],
+            command_index: Some(&self.sub_command_index),
+            output: Output::default(),
+        }
+    }
+
+    /// Variables of the env file, `.env` with `dotenv` when it exists,
+    /// empty without one
+    fn env_file_values(&self) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        let path = match &self.env_file {
+            Some(path) => path.as_str(),
+            None if self.dotenv && Path::new(DOTENV_PATH).is_file() => DOTENV_PATH,
+            None => return Ok(vec![]),
+        };
+        Ok(env_file_vars(&fs::read_to_string(path)?))
+    }
+
+    /// Raw flag values of the config file, keys scoped to a command by the path
+    /// of its names, empty without one or when the file doesn't exist
+    fn config_values(&self) -> Result<Vec<(Vec<String>, String)>, ActionError> {
+        #[cfg(feature = "config-file")]
+        if let Some(path) = &self.config_file {
+            let path = config_file::expanded_home(path);
+            let config_error = |reason: String| ActionError {
+                kind: ActionErrorKind::ConfigFile(path.clone(), reason),
+            };
+            return match fs::read_to_string(&path) {
+                Ok(content) => config_file::config_values(&content).map_err(config_error),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+                Err(e) => Err(config_error(e.to_string())),
+            };
+        }
+        Ok(vec![])
+    }
+
+    /// Run the matched command or the app action,
+    /// returning the path of matched command names and what was done
+    fn dispatch(&self, args: Vec<String>) -> Result<(Vec<String>, Outcome), Box<dyn Error>> {
+        self.dispatch_to(args, Output::default())
+    }
+
+    /// Run like `dispatch`, printing help, version and warnings to `output`
+    fn dispatch_to(
+        &self,
+        args: Vec<String>,
+        output: Output,
+    ) -> Result<(Vec<String>, Outcome), Box<dyn Error>> {
+        let mut args = args;
+        if args.len() > 1 {
+            // the value flags of any command keep their value, args are not
+            // matched to a command yet
+            let mut flags: Vec<&Flag> =
+                self.flags.iter().flatten().chain(self.global_flags.iter().flatten()).collect();
+            flags.extend(self.commands.iter().flat_map(Command::tree_flags));
+            let mut normalized = trimmed_args(&args[1..], &flags);
+            if self.response_files {
+                normalized = expanded_response_files(&normalized, 0)?;
+            }
+            args.truncate(1);
+            args.extend(normalized);
+        }
+
+        // the first arg is the program name, the command (if any) comes next
+        let program_name = args.first().map_or("", |name| name.as_str());
+        let args_v = args.get(1..).unwrap_or(&[]);
+
+        let env_vars = self.env_file_values()?;
+
+        if self.config_dump
+            && args_v
+                .iter()
+                .take_while(|arg| *arg != "--")
+                .any(|arg| arg == "--config-dump")
+        {
+            output.print(&self.config_dump_text(args));
+            return Ok((vec![], Outcome::ConfigDumped));
+        }
+
+        let config = self.config_values()?;
+        let mut dispatch = self.dispatch_settings(program_name);
+        dispatch.env_vars = &env_vars;
+        dispatch.config = &config;
+        dispatch.output = output;
+
+        // a binary symlinked as a command name runs that command
+        if self.dispatch_by_program_name {
+            let bin_name = Path::new(program_name)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            if let Some(command) = self.commands.iter().find(|c| c.matches(&bin_name)) {
+                let (mut command_path, outcome) = command.run_as(&dispatch, args_v.to_vec())?;
+                command_path.insert(0, command.name.clone());
+                return Ok((command_path, outcome));
+            }
+        }
+
+        // global flags, with their value, may come before the command name
+        let leading = dispatch.leading_global_flags(args_v);
+
+        let command = self.find_command(&args_v[leading..]).inspect_err(|e| {
+            if let ActionErrorKind::UnknownCommand(cmd) = &e.kind {
+                output.eprint(&self.not_found_text(cmd));
+            }
+        })?;
+
+        match command {
+            // if there is a command we run it
+            Some(command) => {
+                let mut command_args = args_v[..leading].to_vec();
+                command_args.extend(args_v[leading + 1..].iter().cloned());
+                let (mut command_path, outcome) = command.run_as(&dispatch, command_args)?;
+                command_path.insert(0, command.name.clone());
+                Ok((command_path, outcome))
+            }
+            // if the 2nd arg is not a command we run App action
+            None => {
+                // except if there's a help flag before `--`, no arg with
+                // `no_args_is_help` or no action was configured
+                let help_requested = args_v
+                    .iter()
+                    .take_while(|arg| *arg != "--")
+                    .any(|arg| self.help_flags.contains(arg));
+                let no_args_help = self.no_args_is_help && args_v.is_empty();
+                if let Some(version) = dispatch.version.as_ref().filter(|_| {
+                    !help_requested && dispatch.version_requested(args_v, &self.flags)
+                }) {
+                    output.print(version);
+                    return Ok((vec![], Outcome::VersionShown));
+                }
+                let default_command = self
+                    .default_command
+                    .as_ref()
+                    .filter(|_| !help_requested && !no_args_help);
+                if let Some(name) = default_command {
+                    let command = self.find_command(slice::from_ref(name))?.ok_or_else(|| {
+                        ActionError {
+                            kind: ActionErrorKind::UnknownCommand(name.to_owned()),
+                        }
+                    })?;
+                    let (mut command_path, outcome) = command.run_as(&dispatch, args_v.to_vec())?;
+                    command_path.insert(0, command.name.clone());
+                    return Ok((command_path, outcome));
+                }
+                let command_name = args_v[leading..]
+                    .first()
+                    .filter(|arg| !arg.is_empty() && !arg.starts_with('-'));
+                let action = match self.action {
+                    Some(ref action) if !help_requested && !no_args_help => action,
+                    None if !help_requested && self.no_command_error => {
+                        output.eprint(&self.help_text());
+                        return Err(Box::new(ActionError {
+                            kind: ActionErrorKind::NoCommand,
+                        }));
+                    }
+                    // help shown for a token matching no command is a guide
+                    // after a mistake, it goes to stderr
+                    None if !help_requested && command_name.is_some() => {
+                        output.eprint(&self.help_text());
+                        return Ok((vec![], Outcome::HelpShown));
+                    }
+                    _ => {
+                        output.print(&self.help_text());
+                        return Ok((vec![], Outcome::HelpShown));
+                    }
+                };
+                let context = Context::new(
+                    &dispatch,
+                    command_name.map(|arg| arg.as_str()),
+                    vec![],
+                    args_v,
+                    &self.flags,
+                    self.help_text(),
+                    Some(&self.usage_text()),
+                )?;
+                invoke(action, &context, self.catch_unwind)?;
+                Ok((vec![], Outcome::ActionRan))
+            }
+        }
+    }
+
+    /// Run app with args that may not be valid UTF-8, such as `env::args_os()`.
+    /// `env::args()` panics on invalid UTF-8 before the app is reached, this
+    /// returns an `InvalidUtf8` error identifying the bad argument instead.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use std::env;
+    /// use suihorse::App;
+    ///
+    /// let app = App::new("cli");
+    /// let result = app.run_os(env::args_os().collect());
+    /// ```
+    ///
+    /// ```
+    /// # #[cfg(unix)]
+    /// # {
+    /// use std::ffi::OsString;
+    /// use std::os::unix::ffi::OsStringExt;
+    /// use suihorse::error::{ActionError, ActionErrorKind};
+    /// use suihorse::App;
+    ///
+    /// let app = App::new("cli");
+    /// let args = vec![OsString::from("cli"), OsString::from_vec(vec![b'a', 0xff])];
+    /// let error = app.run_os(args).unwrap_err();
+    /// let error = error.downcast_ref::<ActionError>().unwrap();
+    /// assert_eq!(error.kind, ActionErrorKind::InvalidUtf8(1, "a\u{FFFD}".to_string()));
+    /// # }
+    /// ```
+    pub fn run_os(&self, args: Vec<OsString>) -> Result<(), Box<dyn Error>> {
+        let args = args
+            .into_iter()
+            .enumerate()
+            .map(|(index, arg)| {
+                arg.into_string().map_err(|arg| ActionError {
+                    kind: ActionErrorKind::InvalidUtf8(index, arg.to_string_lossy().into_owned()),
+                })
+            })
+            .collect::<Result<Vec<String>, ActionError>>()?;
+
+        self.run_with_result(args)
+    }
+
+    /// Text printed by `--version` and `-V`, the app name and version,
+    /// None without `version`
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::App;
+    ///
+    /// assert_eq!(App::new("cli").version("1.2.0").version_text().as_deref(), Some("cli 1.2.0"));
+    /// assert_eq!(App::new("cli").version_text(), None);
+    /// ```
+    pub fn version_text(&self) -> Option<String> {
+        self.version
+            .as_ref()
+            .map(|version| format!("{} {}", self.name, version))
+    }
+
+    /// Text printed to stderr in strict mode when `cmd` matches no command,
+    /// with the suggestion of `suggestion_text`, followed by help.
+    /// Help asked for with a help flag goes to stdout.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Help};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build"))
+    ///     .strict(true);
+    ///
+    /// let text = app.not_found_text("biuld");
+    /// assert!(text.starts_with("\"biuld\" is not a command. Did you mean \"build\"?\n\n"));
+    /// assert!(text.ends_with(&app.help_text()));
+    ///
+    /// let text = app.not_found_text("deploy");
+    /// assert!(text.starts_with("\"deploy\" is not a command.\n\n"));
+    /// ```
+    pub fn not_found_text(&self, cmd: &str) -> String {
+        match self.suggestion_text(cmd) {
+            Some(suggestion) => format!(
+                "\"{}\" is not a command. {}\n\n{}",
+                cmd,
+                suggestion,
+                self.help_text()
+            ),
+            None => format!("\"{}\" is not a command.\n\n{}", cmd, self.help_text()),
+        }
+    }
+
+    /// Warning printed to stderr when the command at `path`, of names or aliases
+    /// from the top of the app, runs. None when no command is at `path`, it is
+    /// not `Command::deprecated`, or `no_deprecation_warnings_env` is set.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let add = Command::new("add").deprecated("use `remote set` instead");
+    /// let app = App::new("cli")
+    ///     .command(Command::new("remote").alias("r").command(add));
+    ///
+    /// assert_eq!(
+    ///     app.deprecation_warning_text(&["r", "add"]).as_deref(),
+    ///     Some(r#"Warning: command "add" is deprecated, use `remote set` instead"#)
+    /// );
+    /// assert_eq!(app.deprecation_warning_text(&["remote"]), None);
+    /// assert_eq!(app.deprecation_warning_text(&["deploy"]), None);
+    /// ```
+    pub fn deprecation_warning_text(&self, path: &[&str]) -> Option<String> {
+        let (first, rest) = path.split_first()?;
+        let mut command = self.find_command(&[first.to_string()]).ok().flatten()?;
+        for name in rest {
+            command = command.commands.iter().flatten().find(|c| c.matches(name))?;
+        }
+        command.deprecation_warning_text(self.no_deprecation_warnings_env.as_deref())
+    }
+
+    /// Suggestion of the command whose name or alias is closest to `cmd`, within
+    /// two edits, as shown by `not_found_text`. None when no command is close.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("build"))
+    ///     .command(Command::new("remove").alias("rm"));
+    ///
+    /// assert_eq!(app.suggestion_text("biuld").as_deref(), Some(r#"Did you mean "build"?"#));
+    /// assert_eq!(app.suggestion_text("rn").as_deref(), Some(r#"Did you mean "rm"?"#));
+    /// assert_eq!(app.suggestion_text("deploy"), None);
+    /// ```
+    pub fn suggestion_text(&self, cmd: &str) -> Option<String> {
+        let normalize = |name: &str| {
+            if self.case_insensitive {
+                name.to_lowercase()
+            } else {
+                name.to_owned()
+            }
+        };
+        let token = normalize(cmd);
+        self.commands
+            .iter()
+            .flat_map(|c| iter::once(&c.name).chain(c.alias.iter().flatten()))
+            .map(|name| (edit_distance(&token, &normalize(name)), name))
+            .filter(|(distance, _)| *distance <= 2 && *distance < token.chars().count())
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, name)| format!(r#"Did you mean "{}"?"#, name))
+    }
+
+    /// Command registered by `command` with the name or alias `cmd`
+    fn indexed_command(&self, cmd: &str) -> Option<&Command> {
+        let index = *self.command_index.get(cmd)?;
+        self.commands.get(index).filter(|command| command.matches(cmd))
+    }
+
+    /// Gets the command in the App that matches the first of `args` or return None,
+    /// empty and flag-looking tokens are never commands.
+    /// With prefix matching, an ambiguous prefix is an error.
+    /// In strict mode, a token matching no command is an error.
+    fn find_command(&self, args: &[String]) -> Result<Option<&Command>, ActionError> {
+        let cmd = match args
+            .first()
+            .filter(|cmd| !cmd.is_empty() && !cmd.starts_with('-'))
+        {
+            Some(cmd) => cmd,
+            None => return Ok(None),
+        };
+
+        // names are compared lowercased when matching ignores case,
+        // errors still report the token as typed
+        let normalize = |name: &str| {
+            if self.case_insensitive {
+                name.to_lowercase()
+            } else {
+                name.to_owned()
+            }
+        };
+        let token = normalize(cmd);
+        let names = |command: &Command| {
+            let mut names = vec![normalize(&command.name)];
+            names.extend(command.alias.iter().flatten().map(|a| normalize(a)));
+            names
+        };
+
+        if !self.case_insensitive {
+            if let Some(command) = self.indexed_command(cmd) {
+                return Ok(Some(command));
+            }
+        }
+
+        // `commands` is public, the commands pushed to it directly or edited
+        // after they were set with `command` are only found by scanning
+        if let Some(command) = self
+            .commands
+            .iter()
+            .find(|command| names(command).contains(&token))
+        {
+            return Ok(Some(command));
+        }
+
+        let candidates: Vec<&Command> = if self.prefix_matching {
+            self.commands
+                .iter()
+                .filter(|command| names(command).iter().any(|n| n.starts_with(&token)))
+                .collect()
+        } else {
+            vec![]
+        };
+
+        match candidates[..] {
+            [] if self.strict => Err(ActionError {
+                kind: ActionErrorKind::UnknownCommand(cmd.to_owned()),
+            }),
+            [] => Ok(None),
+            [command] => Ok(Some(command)),
+            _ => Err(ActionError {
+                kind: ActionErrorKind::Ambiguous(
+                    cmd.to_owned(),
+                    candidates.iter().map(|c| c.name.clone()).collect(),
+                ),
+            }),
+        }
+    }
+}
+
+impl Help for App {
+    fn help_text(&self) -> String {
+        let mut header = vec![self.version_text().unwrap_or_else(|| self.name.clone())];
+        header.extend(self.author.iter().cloned());
+        header.extend(self.description.iter().cloned());
+        let help = HelpBuilder::new()
+            .text(header.join("\n"))
+            .help_flags(&self.help_flags)
+            .version_flag(self.version.is_some());
+
+        let flags: Vec<Flag> = self
+            .flags
+            .iter()
+            .chain(self.global_flags.iter())
+            .flatten()
+            .cloned()
+            .collect();
+        help.usage(self.usage_text())
+            .commands(&self.commands)
+            .flags(&flags, false)
+            .render()
+    }
+}
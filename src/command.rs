@@ -1,8 +1,226 @@
-use crate::{Action, Help};
+use crate::app::invoke;
+use crate::error::{ActionError, ActionErrorKind, ExitCode};
+use crate::help::{generated_usage, usage_prefix, DEFAULT_HELP_FLAGS, VERSION_FLAGS};
+use crate::parse::{split_args, trimmed_args};
+use crate::context::StdinReader;
+use crate::{Action, Context, Flag, Help, HelpBuilder};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::error::Error;
+use std::{env, fmt, iter, process};
+use std::io::{self, IsTerminal, Read};
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// What running a command did
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Outcome {
+    /// The action was invoked
+    ActionRan,
+    /// Help was printed instead of invoking the action
+    HelpShown,
+    /// Version was printed instead of invoking the action
+    VersionShown,
+    /// Resolved flags were printed by `--config-dump`
+    ConfigDumped,
+}
+
+/// Positions of sub commands by path of command names from the top of the app,
+/// then by sub command name and alias
+pub(crate) type CommandIndex = HashMap<Vec<String>, HashMap<String, usize>>;
+
+/// Fail when `command` can't be set next to `commands`: with an `EmptyName`
+/// error for an empty name, `Duplicate` for a name already registered and
+/// `AliasCollision` for a name or alias already used by another command
+pub(crate) fn check_registration(
+    commands: &[Command],
+    command: &Command,
+) -> Result<(), ActionError> {
+    if command.name.is_empty() {
+        return Err(ActionError {
+            kind: ActionErrorKind::EmptyName,
+        });
+    }
+    if commands.iter().any(|registered| registered.name == command.name) {
+        return Err(ActionError {
+            kind: ActionErrorKind::Duplicate(command.name.clone()),
+        });
+    }
+    for name in iter::once(&command.name).chain(command.alias.iter().flatten()) {
+        if let Some(registered) = commands.iter().find(|c| c.matches(name)) {
+            return Err(ActionError {
+                kind: ActionErrorKind::AliasCollision(
+                    command.name.clone(),
+                    name.to_owned(),
+                    registered.name.clone(),
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Panic of the builders setting a command rejected by `check_registration`
+pub(crate) fn registration_panic(error: ActionError) -> ! {
+    match error.kind {
+        ActionErrorKind::Duplicate(name) => {
+            panic!(r#"Command name "{}" is already registered."#, name)
+        }
+        ActionErrorKind::EmptyName => panic!("Command name must not be empty."),
+        ActionErrorKind::AliasCollision(command, name, registered) => panic!(
+            r#"Command "{}" uses "{}", already a name or alias of command "{}"."#,
+            command, name, registered
+        ),
+        _ => panic!("{}", error),
+    }
+}
+
+/// Writers of the lines printed while dispatching,
+/// the process stdout and stderr when None
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Output<'a> {
+    pub(crate) stdout: Option<&'a dyn Fn(&str)>,
+    pub(crate) stderr: Option<&'a dyn Fn(&str)>,
+}
+
+impl Output<'_> {
+    /// Print `text` and a newline to stdout
+    pub(crate) fn print(&self, text: &str) {
+        match self.stdout {
+            Some(stdout) => stdout(text),
+            None => println!("{}", text),
+        }
+    }
+
+    /// Print `text` and a newline to stderr
+    pub(crate) fn eprint(&self, text: &str) {
+        match self.stderr {
+            Some(stderr) => stderr(text),
+            None => eprintln!("{}", text),
+        }
+    }
+}
+
+/// App settings threaded down the command tree while dispatching
+#[derive(Clone)]
+pub(crate) struct Dispatch<'a> {
+    /// Program name as invoked, `argv[0]`
+    pub(crate) program_name: &'a str,
+    /// Tokens treated as help triggers
+    pub(crate) help_flags: &'a [String],
+    /// Synthesize the usage of commands without one
+    pub(crate) auto_usage: bool,
+    /// Return a `Panicked` error instead of unwinding when an action panics
+    pub(crate) catch_unwind: bool,
+    /// Return a `NoAction` error for args given to a command without action
+    pub(crate) no_action_error: bool,
+    /// Accept unique prefixes of long flags
+    pub(crate) abbreviate_flags: bool,
+    /// Accept `/name` and `/name:value` for declared flags
+    pub(crate) windows_flags: bool,
+    /// Return an `UnknownFlags` error for `--flags` matching no declared flag
+    pub(crate) deny_unknown_flags: bool,
+    /// Env variable silencing the warning of deprecated commands when set
+    pub(crate) no_deprecation_warnings_env: Option<&'a str>,
+    /// Flags parsed by every command and the app action
+    pub(crate) global_flags: &'a [Flag],
+    /// Names of the commands matched above the current one
+    pub(crate) parents: Vec<String>,
+    /// Reader of `Context::stdin` in place of the process stdin
+    pub(crate) stdin: Option<StdinReader>,
+    /// Text printed by `--version`, `App::version_text`
+    pub(crate) version: Option<String>,
+    /// Prefix of the env variables read for every flag
+    pub(crate) env_prefix: Option<&'a str>,
+    /// Variables of the env file, read after the process env
+    pub(crate) env_vars: &'a [(String, String)],
+    /// Raw flag values of the config file, keyed by flag name,
+    /// scoped to a command by the path of its names before
+    pub(crate) config: &'a [(Vec<String>, String)],
+    /// Positions of the sub commands set when the app was built, none outside of `App`
+    pub(crate) command_index: Option<&'a CommandIndex>,
+    /// Streams help, version and warnings are printed to
+    pub(crate) output: Output<'a>,
+}
+
+impl Dispatch<'_> {
+    /// Whether a version flag is in `args` before `--`,
+    /// unless `flags` or the global flags declare it
+    pub(crate) fn version_requested(&self, args: &[String], flags: &Option<Vec<Flag>>) -> bool {
+        self.version.is_some()
+            && args
+                .iter()
+                .take_while(|arg| *arg != "--")
+                .filter(|arg| VERSION_FLAGS.contains(&arg.as_str()))
+                .any(|arg| !flags.iter().flatten().chain(self.global_flags).any(|f| f.matches(arg)))
+    }
+
+    /// Number of leading `args` that are global flags with their value,
+    /// given before a command name
+    pub(crate) fn leading_global_flags(&self, args: &[String]) -> usize {
+        let mut leading = 0;
+        while let Some(arg) = args.get(leading) {
+            let name = arg.split_once('=').map_or(arg.as_str(), |(name, _)| name);
+            match self.global_flags.iter().find(|f| f.matches(name)) {
+                Some(f) if f.takes_value() && name == arg => leading += 2,
+                Some(_) => leading += 1,
+                None => break,
+            }
+        }
+        leading.min(args.len())
+    }
+
+    /// Value of the env file variable `name`, the last one when repeated
+    pub(crate) fn env_file_value(&self, name: &str) -> Option<String> {
+        self.env_vars
+            .iter()
+            .rev()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.to_owned())
+    }
+
+    /// Config file value of the flag `name` for the command at `path`,
+    /// the key scoped to the command before the bare one
+    pub(crate) fn config_value(&self, path: &[String], name: &str) -> Option<&str> {
+        let mut scoped = path.to_vec();
+        scoped.push(name.to_string());
+        let keys = [(!path.is_empty()).then_some(scoped), Some(vec![name.to_string()])];
+        keys.iter().flatten().find_map(|key| {
+            self.config
+                .iter()
+                .rev()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value.as_str())
+        })
+    }
+}
 
 /// Application command type
-#[derive(Default)]
+///
+/// Fields are public, so a command may also be built as a struct literal,
+/// leaving the rest to `Default`. It runs the same as when built with
+/// the builder methods.
+///
+/// ```
+/// use std::sync::Arc;
+/// use suihorse::{Command, Flag, FlagType};
+///
+/// let command = Command {
+///     name: "cp".to_string(),
+///     flags: Some(vec![Flag::new("force", FlagType::Bool)]),
+///     action: Some(Arc::new(|c| {
+///         assert!(c.bool_flag("force"));
+///         assert_eq!(c.args, vec!["a.txt", "b.txt"]);
+///         Ok(())
+///     })),
+///     ..Command::default()
+/// };
+///
+/// let args = vec!["a.txt", "--force", "b.txt"];
+/// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+/// ```
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Command {
     /// Command name
     pub name: String,
@@ -12,8 +230,56 @@ pub struct Command {
     pub description: Option<String>,
     /// Command usage
     pub usage: Option<String>,
+    /// Synthesize the usage from the flags and args when none is set,
+    /// `App::auto_usage` does it for every command
+    pub auto_usage: bool,
     /// Command action
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub action: Option<Action>,
+    /// Command flags
+    pub flags: Option<Vec<Flag>>,
+    /// List flags alphabetically in help instead of in declaration order
+    pub sort_flags: bool,
+    /// Command sub commands
+    pub commands: Option<Vec<Command>>,
+    /// Heading the command is listed under in help
+    pub category: Option<String>,
+    /// Migration hint of a deprecated command, warned on stderr when it runs
+    pub deprecated: Option<String>,
+    /// Example invocations listed in help
+    pub examples: Option<Vec<String>>,
+    /// Groups of flag names of which at most one may be given
+    pub conflicts: Option<Vec<Vec<String>>>,
+    /// Fewest positional args the action accepts
+    pub min_args: Option<usize>,
+    /// Most positional args the action accepts
+    pub max_args: Option<usize>,
+    /// Names of the leading positional args
+    pub args: Option<Vec<String>>,
+    /// Name of the slot capturing the positional args after the named ones
+    pub variadic_arg: Option<String>,
+    /// Whether the action reads `Context::stdin`, noted in help
+    pub reads_stdin: bool,
+    /// Fail with a `TerminalStdin` error when stdin is a terminal
+    /// instead of running an action that reads it
+    pub terminal_stdin_error: bool,
+}
+
+impl fmt::Debug for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Command")
+            .field("name", &self.name)
+            .field("alias", &self.alias)
+            .field("description", &self.description)
+            .field("usage", &self.usage)
+            .field("action", &self.action.as_ref().map(|_| format_args!("<action>")))
+            .field("flags", &self.flag_names())
+            .field("commands", &self.commands.iter().flatten().map(|c| &c.name).collect::<Vec<_>>())
+            .field("category", &self.category)
+            .field("deprecated", &self.deprecated)
+            .field("reads_stdin", &self.reads_stdin)
+            .finish()
+    }
 }
 
 impl Command {
@@ -33,6 +299,38 @@ impl Command {
         }
     }
 
+    /// Create new instance of `Command` from its main parts,
+    /// empty `flags` and `commands` are the same as none
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    /// use suihorse::{Action, Command, Flag, FlagType};
+    ///
+    /// let action: Action = Arc::new(|c| {
+    ///     assert!(c.bool_flag("all"));
+    ///     Ok(())
+    /// });
+    /// let command = Command::from_parts("ls", Some(action), vec![Flag::new("all", FlagType::Bool)], vec![]);
+    ///
+    /// command.run_with_result(vec!["--all".to_string()]).unwrap();
+    /// ```
+    pub fn from_parts<T: Into<String>>(
+        name: T,
+        action: Option<Action>,
+        flags: Vec<Flag>,
+        commands: Vec<Command>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            action,
+            flags: Some(flags).filter(|flags| !flags.is_empty()),
+            commands: Some(commands).filter(|commands| !commands.is_empty()),
+            ..Self::default()
+        }
+    }
+
     /// Set description of the command
     ///
     /// Example
@@ -63,19 +361,61 @@ impl Command {
         self
     }
 
-    /// Set action of the command
+    /// Synthesize the usage from the flags and args when none is set
     ///
     /// Example
     ///
     /// ```
-    /// use suihorse::{Command, Context, Action};
+    /// use suihorse::Command;
+    ///
+    /// let command = Command::new("cp")
+    ///     .arg("src")
+    ///     .arg("dest")
+    ///     .auto_usage(true);
     ///
-    /// let action: Action = |c: &Context| println!("{:?}", c.args);
+    /// assert_eq!(command.effective_usage().as_deref(), Some("cp <src> <dest>"));
+    /// ```
+    pub fn auto_usage(mut self, auto_usage: bool) -> Self {
+        self.auto_usage = auto_usage;
+        self
+    }
+
+    /// Set action of the command, closures may capture their environment
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Context};
+    ///
+    /// let greeting = String::from("Hello");
     /// let command = Command::new("cmd")
-    ///     .action(action);
+    ///     .action(move |c: &Context| println!("{} {:?}", greeting, c.args));
+    ///
     /// ```
-    pub fn action(mut self, action: Action) -> Self {
-        self.action = Some(action);
+    pub fn action<F: Fn(&Context) + Send + Sync + 'static>(mut self, action: F) -> Self {
+        self.action = Some(Arc::new(move |c| {
+            action(c);
+            Ok(())
+        }));
+        self
+    }
+
+    /// Set fallible action of the command, an error it returns is returned by `run_with_result`
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::Command;
+    ///
+    /// let command = Command::new("cmd")
+    ///     .action_with_result(|c| Err(format!("cannot handle {:?}", c.args).into()));
+    /// assert!(command.run_with_result(vec!["arg".to_string()]).is_err());
+    /// ```
+    pub fn action_with_result<F>(mut self, action: F) -> Self
+    where
+        F: Fn(&Context) -> Result<(), Box<dyn Error>> + Send + Sync + 'static,
+    {
+        self.action = Some(Arc::new(action));
         self
     }
 
@@ -98,52 +438,976 @@ impl Command {
         self
     }
 
-    fn normalized_args(raw_args: Vec<String>) -> Vec<String> {
-        raw_args.iter().fold(Vec::<String>::new(), |mut acc, cur| {
-            if cur.starts_with('-') && cur.contains('=') {
-                let mut splitted_flag: Vec<String> =
-                    cur.splitn(2, '=').map(|s| s.to_owned()).collect();
-                acc.append(&mut splitted_flag);
-            } else {
-                acc.push(cur.to_owned());
-            }
-            acc
-        })
+    /// Remove every alias of the command
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::Command;
+    ///
+    /// let template = Command::new("deploy")
+    ///     .aliases(["d", "ship"]);
+    /// let command = template.clone().clear_aliases();
+    ///
+    /// assert_eq!(command.alias, None);
+    /// assert_eq!(template.alias, Some(vec!["d".to_string(), "ship".to_string()]));
+    /// ```
+    pub fn clear_aliases(mut self) -> Self {
+        self.alias = None;
+        self
+    }
+
+    /// Replace every alias of the command
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let template = Command::new("deploy")
+    ///     .aliases(["d", "ship"]);
+    /// let mut staging = template.clone().set_aliases(vec!["s"]);
+    /// staging.name = "deploy-staging".to_string();
+    ///
+    /// assert_eq!(staging.alias, Some(vec!["s".to_string()]));
+    /// let app = App::new("cli")
+    ///     .command(template)
+    ///     .command(staging);
+    /// ```
+    ///
+    /// A reconfigured clone still goes through the duplicate check
+    ///
+    /// ```should_panic
+    /// use suihorse::{App, Command};
+    ///
+    /// let template = Command::new("deploy");
+    /// let app = App::new("cli")
+    ///     .command(template.clone().set_aliases(vec!["d"]))
+    ///     .command(template);
+    /// ```
+    pub fn set_aliases<I: IntoIterator<Item = T>, T: Into<String>>(self, names: I) -> Self {
+        self.clear_aliases().aliases(names)
     }
 
-    /// Call this function only from `App`
+    /// Set several aliases of the command at once
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let command = Command::new("remove")
+    ///     .aliases(["rm", "del", "r"])
+    ///     .action(|c| assert_eq!(c.args, vec!["file"]));
+    /// let app = App::new("cli")
+    ///     .command(command)
+    ///     .action(|_| unreachable!());
+    ///
+    /// for alias in ["rm", "del", "r"] {
+    ///     let args = vec!["cli", alias, "file"];
+    ///     app.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// }
+    /// ```
+    pub fn aliases<I: IntoIterator<Item = T>, T: Into<String>>(mut self, names: I) -> Self {
+        for name in names {
+            self = self.alias(name);
+        }
+        self
+    }
+
+    /// Set flag of the command
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("cmd")
+    ///     .flag(Flag::new("verbose", FlagType::Bool).alias("v"));
+    /// ```
+    pub fn flag(mut self, flag: Flag) -> Self {
+        if let Some(ref mut flags) = self.flags {
+            (*flags).push(flag);
+        } else {
+            self.flags = Some(vec![flag]);
+        }
+        self
+    }
+
+    /// Set sub command of the command, dispatched when its name or alias
+    /// follows the command name
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let add = Command::new("add")
+    ///     .action(|c| assert_eq!(c.args, vec!["origin"]));
+    /// let remote = Command::new("remote")
+    ///     .command(add);
+    /// let app = App::new("cli")
+    ///     .command(remote);
+    ///
+    /// let args = vec!["cli", "remote", "add", "origin"];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert_eq!(invocation.command_path, vec!["remote", "add"]);
+    /// ```
+    ///
+    /// Help is shown for the deepest matched command
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Outcome};
+    ///
+    /// let add = Command::new("add")
+    ///     .action(|_| unreachable!());
+    /// let remote = Command::new("remote")
+    ///     .action(|_| unreachable!())
+    ///     .command(add);
+    /// let app = App::new("cli")
+    ///     .command(remote);
+    ///
+    /// let args = vec!["cli", "remote", "add", "-h"];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert_eq!(invocation.command_path, vec!["remote", "add"]);
+    /// assert_eq!(invocation.outcome, Outcome::HelpShown);
+    /// ```
+    ///
+    /// Sub commands are found by alias at any depth, as are those pushed to
+    /// `commands` after the app was built
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let origin = Command::new("origin")
+    ///     .alias("o")
+    ///     .action(|_| {});
+    /// let add = Command::new("add")
+    ///     .command(origin);
+    /// let remote = Command::new("remote")
+    ///     .command(add);
+    /// let mut app = App::new("cli")
+    ///     .command(remote);
+    ///
+    /// let args = vec!["cli", "remote", "add", "o"];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert_eq!(invocation.command_path, vec!["remote", "add", "origin"]);
+    ///
+    /// let rm = Command::new("rm").action(|_| {});
+    /// app.commands[0].commands.as_mut().unwrap().push(rm);
+    /// let args = vec!["cli", "remote", "rm"];
+    /// let invocation = app.run_logged(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert_eq!(invocation.command_path, vec!["remote", "rm"]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Like `App::command`, names and aliases are unique across the sub commands,
+    /// so none is unreachable.
+    ///
+    /// ```should_panic
+    /// use suihorse::Command;
+    ///
+    /// let remote = Command::new("remote")
+    ///     .command(Command::new("add"))
+    ///     .command(Command::new("append").alias("add"));
+    /// ```
+    ///
+    /// ```should_panic
+    /// use suihorse::Command;
+    ///
+    /// let remote = Command::new("remote")
+    ///     .command(Command::new("add").alias("a"))
+    ///     .command(Command::new("append").alias("a"));
+    /// ```
+    ///
+    /// ```should_panic
+    /// use suihorse::Command;
+    ///
+    /// let remote = Command::new("remote")
+    ///     .command(Command::new(""));
+    /// ```
+    pub fn command(mut self, command: Command) -> Self {
+        if let Err(e) = check_registration(self.commands.as_deref().unwrap_or(&[]), &command) {
+            registration_panic(e);
+        }
+        if let Some(ref mut commands) = self.commands {
+            (*commands).push(command);
+        } else {
+            self.commands = Some(vec![command]);
+        }
+        self
+    }
+
+    /// Add a group of flags of which at most one may be given on the command line,
+    /// giving two returns a `Conflict` error naming them
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::error::{ActionError, ActionErrorKind};
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("show")
+    ///     .flag(Flag::new("json", FlagType::Bool))
+    ///     .flag(Flag::new("yaml", FlagType::Bool))
+    ///     .conflicts(&["json", "yaml"])
+    ///     .action(|c| assert!(c.bool_flag("json")));
+    ///
+    /// command.run_with_result(vec!["--json".to_string()]).unwrap();
+    ///
+    /// let args = vec!["--yaml".to_string(), "--json".to_string()];
+    /// let error = command.run_with_result(args).unwrap_err();
+    /// assert_eq!(
+    ///     error.downcast_ref::<ActionError>().unwrap().kind,
+    ///     ActionErrorKind::Conflict("json".to_string(), "yaml".to_string())
+    /// );
+    /// ```
+    pub fn conflicts(mut self, names: &[&str]) -> Self {
+        let group = names.iter().map(|name| name.to_string()).collect();
+        if let Some(ref mut conflicts) = self.conflicts {
+            (*conflicts).push(group);
+        } else {
+            self.conflicts = Some(vec![group]);
+        }
+        self
+    }
+
+    /// Name the next positional arg, read with `Context::arg`.
+    /// Named args are required, they raise `min_args` to their count.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::Command;
+    ///
+    /// let command = Command::new("cp")
+    ///     .arg("src")
+    ///     .arg("dest")
+    ///     .max_args(3)
+    ///     .action(|c| {
+    ///         assert_eq!(c.arg("src"), Some("a.txt"));
+    ///         assert_eq!(c.arg("dest"), Some("b.txt"));
+    ///     });
+    ///
+    /// command.run_with_result(vec!["a.txt".to_string(), "b.txt".to_string()]).unwrap();
+    /// let error = command.run_with_result(vec!["a.txt".to_string()]).unwrap_err();
+    /// assert_eq!(error.to_string(), "InvalidArgs: expected at least 2 args, got 1");
+    /// ```
+    pub fn arg<T: Into<String>>(mut self, name: T) -> Self {
+        if let Some(ref mut args) = self.args {
+            (*args).push(name.into());
+        } else {
+            self.args = Some(vec![name.into()]);
+        }
+        self
+    }
+
+    /// Name the slot capturing every positional arg after the named ones,
+    /// including the args after `--`, read with `Context::variadic`
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("exec")
+    ///     .flag(Flag::new("quiet", FlagType::Bool))
+    ///     .arg("cmd")
+    ///     .variadic_arg("rest")
+    ///     .action(|c| {
+    ///         assert_eq!(c.arg("cmd"), Some("ls"));
+    ///         assert_eq!(c.variadic("rest"), ["-l", "--all"]);
+    ///     });
+    ///
+    /// let args = vec!["--quiet", "ls", "--", "-l", "--all"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    ///
+    /// // the named args are still required, the variadic slot may be empty
+    /// let error = command.run_with_result(vec!["--quiet".to_string()]).unwrap_err();
+    /// assert_eq!(error.to_string(), "InvalidArgs: expected at least 1 args, got 0");
+    /// ```
+    pub fn variadic_arg<T: Into<String>>(mut self, name: T) -> Self {
+        self.variadic_arg = Some(name.into());
+        self
+    }
+
+    /// Set fewest positional args the action accepts, counted without flags,
+    /// fewer return an `InvalidArgs` error. The named args are the minimum
+    /// when there are more of them.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("rm")
+    ///     .flag(Flag::new("force", FlagType::Bool))
+    ///     .min_args(1)
+    ///     .action(|c| println!("{:?}", c.args));
+    ///
+    /// command.run_with_result(vec!["a.txt".to_string()]).unwrap();
+    /// let error = command.run_with_result(vec!["--force".to_string()]).unwrap_err();
+    /// assert_eq!(error.to_string(), "InvalidArgs: expected at least 1 args, got 0");
+    /// ```
+    pub fn min_args(mut self, min_args: usize) -> Self {
+        self.min_args = Some(min_args);
+        self
+    }
+
+    /// Set most positional args the action accepts, counted without flags,
+    /// more return an `InvalidArgs` error
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::Command;
+    ///
+    /// let command = Command::new("log")
+    ///     .max_args(2)
+    ///     .action(|c| println!("{:?}", c.args));
+    ///
+    /// command.run_with_result(vec![]).unwrap();
+    /// command.run_with_result(vec!["a".to_string(), "b".to_string()]).unwrap();
+    /// let args = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+    /// let error = command.run_with_result(args).unwrap_err();
+    /// assert_eq!(error.to_string(), "InvalidArgs: expected at most 2 args, got 3");
+    /// ```
+    pub fn max_args(mut self, max_args: usize) -> Self {
+        self.max_args = Some(max_args);
+        self
+    }
+
+    /// Add an example invocation listed in help after the usage
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Help};
+    ///
+    /// let command = Command::new("cp")
+    ///     .usage("cli cp <src> <dest>")
+    ///     .example("cli cp a.txt b.txt")
+    ///     .example("cli cp -r dir backup");
+    ///
+    /// assert!(command
+    ///     .help_text()
+    ///     .contains("Examples:\n\tcli cp a.txt b.txt\n\tcli cp -r dir backup\n\n"));
+    /// ```
+    pub fn example<T: Into<String>>(mut self, example: T) -> Self {
+        if let Some(ref mut examples) = self.examples {
+            (*examples).push(example.into());
+        } else {
+            self.examples = Some(vec![example.into()]);
+        }
+        self
+    }
+
+    /// Set category the command is listed under in help,
+    /// uncategorized commands stay under `Commands:`
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Help};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("deploy").category("Deployment"))
+    ///     .command(Command::new("init"))
+    ///     .command(Command::new("rollback").category("Deployment"));
+    ///
+    /// let text = app.help_text();
+    /// assert!(text.contains("\nCommands:\n\tinit : \n"));
+    /// assert!(text.contains("\nDeployment:\n\tdeploy   : \n\trollback : \n"));
+    /// ```
+    pub fn category<T: Into<String>>(mut self, category: T) -> Self {
+        self.category = Some(category.into());
+        self
+    }
+
+    /// Mark the command deprecated, it still runs after printing a warning with
+    /// `message` to stderr, `App::deprecation_warning_text`, and is listed as
+    /// `(deprecated)` in help.
+    /// See `App::no_deprecation_warnings_env` to silence the warning.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Help};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("sync").description("sync files").action(|_| {}))
+    ///     .command(
+    ///         Command::new("pull")
+    ///             .description("pull files")
+    ///             .deprecated("use `sync` instead")
+    ///             .action(|c| assert_eq!(c.args, vec!["docs"])),
+    ///     )
+    ///     .no_deprecation_warnings_env("CLI_NO_DEPRECATION_WARNINGS");
+    ///
+    /// std::env::remove_var("CLI_NO_DEPRECATION_WARNINGS");
+    /// assert_eq!(
+    ///     app.deprecation_warning_text(&["pull"]).as_deref(),
+    ///     Some(r#"Warning: command "pull" is deprecated, use `sync` instead"#)
+    /// );
+    /// assert_eq!(app.deprecation_warning_text(&["sync"]), None);
+    /// app.run_slice(&["cli", "pull", "docs"]).unwrap();
+    ///
+    /// std::env::set_var("CLI_NO_DEPRECATION_WARNINGS", "1");
+    /// assert_eq!(app.deprecation_warning_text(&["pull"]), None);
+    ///
+    /// let text = app.help_text();
+    /// assert!(text.contains("\tsync : sync files\n\tpull : (deprecated) pull files\n"));
+    /// ```
+    pub fn deprecated<T: Into<String>>(mut self, message: T) -> Self {
+        self.deprecated = Some(message.into());
+        self
+    }
+
+    /// List flags alphabetically in help instead of in declaration order,
+    /// `-h, --help` always comes last
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("cmd")
+    ///     .flag(Flag::new("verbose", FlagType::Bool))
+    ///     .flag(Flag::new("color", FlagType::Bool))
+    ///     .sort_flags(true);
+    /// ```
+    pub fn sort_flags(mut self, sort_flags: bool) -> Self {
+        self.sort_flags = sort_flags;
+        self
+    }
+
+    /// Declare the action reads `Context::stdin`, such as in a pipe,
+    /// which the help of the command mentions
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Help};
+    ///
+    /// let command = Command::new("sort")
+    ///     .reads_stdin(true)
+    ///     .action(|c| println!("{}", c.stdin()));
+    ///
+    /// assert!(command.help_text().contains("Input:\n\tread from stdin\n"));
+    /// ```
+    pub fn reads_stdin(mut self, reads_stdin: bool) -> Self {
+        self.reads_stdin = reads_stdin;
+        self
+    }
+
+    /// Fail with a `TerminalStdin` error, instead of running the action, when the
+    /// command reads stdin and stdin is a terminal rather than a pipe or a file.
+    /// Off by default, `Context::stdin` is empty then.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::Command;
+    ///
+    /// let command = Command::new("sort")
+    ///     .reads_stdin(true)
+    ///     .terminal_stdin_error(true)
+    ///     .action(|c| assert_eq!(c.stdin(), "b\na\n"));
+    ///
+    /// // a given reader is never a terminal
+    /// let input: &[u8] = b"b\na\n";
+    /// command.run_with_stdin(vec![], input).unwrap();
+    /// ```
+    pub fn terminal_stdin_error(mut self, terminal_stdin_error: bool) -> Self {
+        self.terminal_stdin_error = terminal_stdin_error;
+        self
+    }
+
+    /// Aliases of the command in the order they were added
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::Command;
+    ///
+    /// let command = Command::new("remove").alias("rm").alias("del");
+    /// assert_eq!(command.alias_names(), ["rm", "del"]);
+    /// assert!(Command::new("build").alias_names().is_empty());
+    /// ```
+    pub fn alias_names(&self) -> &[String] {
+        self.alias.as_deref().unwrap_or(&[])
+    }
+
+    /// Names of the command flags in declaration order, without dashes
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("build")
+    ///     .flag(Flag::new("release", FlagType::Bool))
+    ///     .flag(Flag::new("jobs", FlagType::Int));
+    /// assert_eq!(command.flag_names(), vec!["release", "jobs"]);
+    /// ```
+    pub fn flag_names(&self) -> Vec<&str> {
+        self.flags.iter().flatten().map(|f| f.name.as_str()).collect()
+    }
+
+    /// Run the command on its own with `args`, the args following its name,
+    /// such as to test a command without building an `App`. Sub commands and
+    /// flags are dispatched as from `App` with its default settings, and the
+    /// action gets a `Context` with an empty program name.
+    /// An error returned by the action is returned as is.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("greet")
+    ///     .flag(Flag::new("loud", FlagType::Bool))
+    ///     .action_with_result(|c| {
+    ///         assert_eq!(c.program_name(), "");
+    ///         assert!(c.bool_flag("loud"));
+    ///         match c.args.first() {
+    ///             Some(name) => Ok(println!("HELLO {}", name.to_uppercase())),
+    ///             None => Err("missing name".into()),
+    ///         }
+    ///     });
+    ///
+    /// command.run_with_result(vec!["--loud".to_string(), "ann".to_string()]).unwrap();
+    /// let error = command.run_with_result(vec!["--loud".to_string()]).unwrap_err();
+    /// assert_eq!(error.to_string(), "missing name");
+    /// ```
+    ///
+    /// Everything after a standalone `--` is passed to the action verbatim
+    ///
+    /// ```
+    /// use suihorse::Command;
+    ///
+    /// let command = Command::new("run")
+    ///     .action(|c| assert_eq!(c.args, vec!["--not-a-flag", "value"]));
+    ///
+    /// let args = vec!["--", "--not-a-flag", "value"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// ```
     pub fn run_with_result(&self, args: Vec<String>) -> Result<(), Box<dyn Error>> {
-        let args = Self::normalized_args(args);
+        self.run_with_outcome(args).map(|_| ())
+    }
+
+    /// Run the command as a whole program, the first of `args` being the program
+    /// name as in `env::args()`. It exits the process with the code of an
+    /// `ExitCode` error returned by the action and panics on any other error,
+    /// like `App::run`.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("greet")
+    ///     .flag(Flag::new("loud", FlagType::Bool).alias("l"))
+    ///     .action(|c| {
+    ///         assert!(c.bool_flag("loud"));
+    ///         assert_eq!(c.args, vec!["ann"]);
+    ///     });
+    ///
+    /// let args = vec!["greet", "-l", "ann"];
+    /// command.run(args.into_iter().map(String::from).collect());
+    ///
+    /// let args = vec!["greet", "--help"];
+    /// command.run(args.into_iter().map(String::from).collect());
+    /// ```
+    pub fn run(&self, args: Vec<String>) {
+        if let Err(e) = self.run_with_result(args.into_iter().skip(1).collect()) {
+            match e.downcast_ref::<ExitCode>() {
+                Some(ExitCode(code)) => process::exit(*code),
+                None => panic!("{}", e),
+            }
+        }
+    }
 
-        match self.action {
+    /// Run the command as a whole program with the args of the process,
+    /// same as `run(env::args().collect())`
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::Command;
+    ///
+    /// Command::new("greet")
+    ///     .action(|c| println!("Hello, {:?}", c.args))
+    ///     .run_env();
+    /// ```
+    pub fn run_env(&self) {
+        self.run(env::args().collect());
+    }
+
+    /// Same as `run_with_result`, reporting whether the action ran or help was shown
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Outcome};
+    ///
+    /// let command = Command::new("run")
+    ///     .action(|c| println!("{:?}", c.args));
+    /// let outcome = command.run_with_outcome(vec!["arg".to_string()]).unwrap();
+    /// assert_eq!(outcome, Outcome::ActionRan);
+    ///
+    /// let outcome = command.run_with_outcome(vec!["-h".to_string()]).unwrap();
+    /// assert_eq!(outcome, Outcome::HelpShown);
+    ///
+    /// let command = Command::new("run");
+    /// let outcome = command.run_with_outcome(vec![]).unwrap();
+    /// assert_eq!(outcome, Outcome::HelpShown);
+    /// ```
+    pub fn run_with_outcome(&self, args: Vec<String>) -> Result<Outcome, Box<dyn Error>> {
+        self.run_standalone(args, None)
+    }
+
+    /// Same as `run_with_result`, the action reading `input` as `Context::stdin`
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::Command;
+    ///
+    /// let command = Command::new("upper")
+    ///     .reads_stdin(true)
+    ///     .action(|c| assert_eq!(c.stdin().to_uppercase(), "HELLO"));
+    ///
+    /// let input: &[u8] = b"hello";
+    /// command.run_with_stdin(vec![], input).unwrap();
+    /// ```
+    pub fn run_with_stdin<R: Read + 'static>(
+        &self,
+        args: Vec<String>,
+        input: R,
+    ) -> Result<(), Box<dyn Error>> {
+        self.run_standalone(args, Some(Rc::new(RefCell::new(input)))).map(|_| ())
+    }
+
+    /// Run the command outside of an `App`, with the default settings
+    fn run_standalone(
+        &self,
+        args: Vec<String>,
+        stdin: Option<StdinReader>,
+    ) -> Result<Outcome, Box<dyn Error>> {
+        let help_flags: Vec<String> = DEFAULT_HELP_FLAGS.iter().map(|f| f.to_string()).collect();
+        let dispatch = Dispatch {
+            program_name: "",
+            help_flags: &help_flags,
+            auto_usage: true,
+            catch_unwind: false,
+            no_action_error: false,
+            abbreviate_flags: false,
+            windows_flags: false,
+            deny_unknown_flags: false,
+            no_deprecation_warnings_env: None,
+            global_flags: &[],
+            parents: vec![],
+            stdin,
+            version: None,
+            env_prefix: None,
+            env_vars: &[],
+            config: &[],
+            command_index: None,
+            output: Output::default(),
+        };
+        let args = trimmed_args(&args, &self.tree_flags());
+        self.run_as(&dispatch, args).map(|(_, outcome)| outcome)
+    }
+
+    /// Run the command dispatched by `App`,
+    /// returning the path of matched sub command names and what was done
+    pub(crate) fn run_as(
+        &self,
+        dispatch: &Dispatch,
+        args: Vec<String>,
+    ) -> Result<(Vec<String>, Outcome), Box<dyn Error>> {
+        // global flags given before the sub command name are passed down
+        let leading = dispatch.leading_global_flags(&args);
+        if let Some(command) = self.find_command(dispatch, &args[leading..]) {
+            let mut child = dispatch.clone();
+            child.parents.push(self.name.clone());
+            let mut command_args = args[..leading].to_vec();
+            command_args.extend(args[leading + 1..].iter().cloned());
+            let (mut command_path, outcome) = command.run_as(&child, command_args)?;
+            command_path.insert(0, command.name.clone());
+            return Ok((command_path, outcome));
+        }
+
+        // help flags after `--` are positional
+        let help_requested = args
+            .iter()
+            .take_while(|arg| *arg != "--")
+            .any(|arg| dispatch.help_flags.contains(arg));
+
+        if let Some(version) = dispatch.version.as_ref().filter(|_| {
+            !help_requested && dispatch.version_requested(&args, &self.flags)
+        }) {
+            dispatch.output.print(version);
+            return Ok((vec![], Outcome::VersionShown));
+        }
+
+        match &self.action {
             Some(action) => {
-                if args.contains(&"-h".to_string()) || args.contains(&"--help".to_string()) {
-                    self.help();
-                    return Ok(());
+                if help_requested {
+                    self.dispatched_help(dispatch);
+                    return Ok((vec![], Outcome::HelpShown));
+                }
+                let usage = self.shown_usage(dispatch.program_name, &dispatch.parents, dispatch.auto_usage);
+                let mut command_path = dispatch.parents.clone();
+                command_path.push(self.name.clone());
+                let mut context = Context::new(
+                    dispatch,
+                    Some(&self.name),
+                    command_path,
+                    &args,
+                    &self.flags,
+                    self.dispatched_help_text(dispatch),
+                    usage.as_deref(),
+                )?;
+                context.bind_args(self.args.as_deref().unwrap_or(&[]), self.variadic_arg.as_deref());
+                self.check_conflicts(&context)?;
+                self.check_arg_count(&context)?;
+                if self.reads_stdin
+                    && self.terminal_stdin_error
+                    && dispatch.stdin.is_none()
+                    && io::stdin().is_terminal()
+                {
+                    return Err(Box::new(ActionError {
+                        kind: ActionErrorKind::TerminalStdin(self.name.clone()),
+                    }));
                 }
-                action(args.to_vec());
-                return Ok(());
-            },
+                self.warn_deprecated(dispatch);
+                invoke(action, &context, dispatch.catch_unwind)?;
+                Ok((vec![], Outcome::ActionRan))
+            }
             None => {
-                self.help();
-                return Ok(());
+                let declared: Vec<&Flag> =
+                    self.flags.iter().flatten().chain(dispatch.global_flags).collect();
+                let (flag_args, trailing) = split_args(&args, &declared);
+                let has_positionals =
+                    !trailing.is_empty() || flag_args.iter().any(|arg| !arg.starts_with('-'));
+                if dispatch.no_action_error && !help_requested && has_positionals {
+                    return Err(Box::new(ActionError {
+                        kind: ActionErrorKind::NoAction(self.name.clone()),
+                    }));
+                }
+                // help shown for args matching no sub command is a guide
+                // after a mistake, it goes to stderr
+                if !help_requested && has_positionals {
+                    dispatch.output.eprint(&self.dispatched_help_text(dispatch));
+                } else {
+                    self.dispatched_help(dispatch);
+                }
+                Ok((vec![], Outcome::HelpShown))
             }
         }
     }
-}
 
-impl Help for Command {
-    fn help_text(&self) -> String {
-        let mut text = String::new();
+    /// Print the deprecation warning of the command to stderr,
+    /// unless the env variable silencing it is set
+    fn warn_deprecated(&self, dispatch: &Dispatch) {
+        if let Some(text) = self.deprecation_warning_text(dispatch.no_deprecation_warnings_env) {
+            dispatch.output.eprint(&text);
+        }
+    }
+
+    /// Deprecation warning of the command, None when it is not deprecated
+    /// or the env variable `no_deprecation_warnings_env` is set
+    pub(crate) fn deprecation_warning_text(
+        &self,
+        no_deprecation_warnings_env: Option<&str>,
+    ) -> Option<String> {
+        let message = self.deprecated.as_ref()?;
+        let silenced = no_deprecation_warnings_env
+            .is_some_and(|name| env::var_os(name).is_some_and(|value| !value.is_empty()));
+        if silenced {
+            return None;
+        }
+        Some(format!(r#"Warning: command "{}" is deprecated, {}"#, self.name, message))
+    }
+
+    /// Fail on the first two flags of a `conflicts` group given together
+    fn check_conflicts(&self, context: &Context) -> Result<(), ActionError> {
+        for group in self.conflicts.iter().flatten() {
+            let mut present = group.iter().filter(|name| context.flag_present(name));
+            if let (Some(first), Some(second)) = (present.next(), present.next()) {
+                return Err(ActionError {
+                    kind: ActionErrorKind::Conflict(first.to_owned(), second.to_owned()),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Fail when the positional args are fewer than `min_args` or the named args,
+    /// or more than `max_args`
+    fn check_arg_count(&self, context: &Context) -> Result<(), ActionError> {
+        let count = context.args.len();
+        let named = self.args.as_ref().map_or(0, |args| args.len());
+        let min_args = self.min_args.unwrap_or(0).max(named);
+        let message = match (min_args, self.max_args) {
+            (min, _) if count < min => format!("expected at least {} args, got {}", min, count),
+            (_, Some(max)) if count > max => format!("expected at most {} args, got {}", max, count),
+            _ => return Ok(()),
+        };
+        Err(ActionError {
+            kind: ActionErrorKind::InvalidArgs(message),
+        })
+    }
+
+    /// Print help as shown by `dispatched_help_text` to stdout
+    fn dispatched_help(&self, dispatch: &Dispatch) {
+        dispatch.output.print(&self.dispatched_help_text(dispatch));
+    }
+
+    /// Help text with the usage resolved by `shown_usage`
+    pub(crate) fn dispatched_help_text(&self, dispatch: &Dispatch) -> String {
+        let usage = self.shown_usage(dispatch.program_name, &dispatch.parents, dispatch.auto_usage);
+        self.help_text_with_usage(
+            usage.as_deref(),
+            dispatch.global_flags,
+            dispatch.help_flags,
+            dispatch.version.is_some(),
+        )
+    }
+
+    /// Usage shown in the help of the command: the explicit usage, else the synthesized
+    /// one if `auto_usage` is on, else none and help has no `Usage:` section
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType, Help};
+    ///
+    /// let command = Command::new("ls").flag(Flag::new("all", FlagType::Bool));
+    /// assert_eq!(command.effective_usage(), None);
+    /// assert!(!command.help_text().contains("Usage:"));
+    ///
+    /// let command = command.auto_usage(true);
+    /// assert_eq!(command.effective_usage().as_deref(), Some("ls [--all]"));
+    /// assert!(command.help_text().contains("Usage:\n\tls [--all]\n"));
+    ///
+    /// let command = command.usage("ls [--all] [dir]");
+    /// assert_eq!(command.effective_usage().as_deref(), Some("ls [--all] [dir]"));
+    /// assert!(command.help_text().contains("Usage:\n\tls [--all] [dir]\n"));
+    /// ```
+    pub fn effective_usage(&self) -> Option<String> {
+        self.shown_usage("", &[], false)
+    }
+
+    /// Explicit usage of the command, else the synthesized one if `auto_usage`
+    /// is on for the app or the command.
+    /// An explicit usage starting with the command name, such as `add [args]`,
+    /// is prefixed with the binary and parent command names.
+    pub(crate) fn shown_usage(
+        &self,
+        program_name: &str,
+        parents: &[String],
+        auto_usage: bool,
+    ) -> Option<String> {
+        match &self.usage {
+            Some(usage) if usage.split_whitespace().next() == Some(&self.name) => {
+                let mut words = usage_prefix(program_name, parents);
+                words.push(usage.to_owned());
+                Some(words.join(" "))
+            }
+            Some(usage) => Some(usage.to_owned()),
+            None if auto_usage || self.auto_usage => Some(generated_usage(program_name, parents, self)),
+            None => None,
+        }
+    }
+
+    /// Help text of the command showing `usage`, listing `global_flags` after its flags,
+    /// then the `help_flags` and the version flag when `version_flag`
+    fn help_text_with_usage(
+        &self,
+        usage: Option<&str>,
+        global_flags: &[Flag],
+        help_flags: &[String],
+        version_flag: bool,
+    ) -> String {
+        let mut help = HelpBuilder::new()
+            .help_flags(help_flags)
+            .version_flag(version_flag);
 
         if let Some(description) = &self.description {
-            text += &format!("Description:\n\t{}\n\n", description);
+            help = help.description(description);
+        }
+
+        if let Some(usage) = usage {
+            help = help.usage(usage);
+        }
+
+        if self.reads_stdin {
+            help = help.section("Input", "read from stdin");
+        }
+
+        if let Some(examples) = &self.examples {
+            help = help.examples(examples);
+        }
+
+        if let Some(commands) = &self.commands {
+            help = help.commands(commands);
+        }
+
+        let flags: Vec<Flag> = self.flags.iter().flatten().chain(global_flags).cloned().collect();
+        help.flags(&flags, self.sort_flags).render()
+    }
+
+    /// Gets the sub command matching the first of `args` or return None,
+    /// empty and flag-looking tokens are never commands.
+    /// The sub commands indexed when the app was built are looked up first.
+    pub(crate) fn find_command(&self, dispatch: &Dispatch, args: &[String]) -> Option<&Command> {
+        let cmd = args
+            .first()
+            .filter(|cmd| !cmd.is_empty() && !cmd.starts_with('-'))?;
+        let commands = self.commands.as_deref().unwrap_or(&[]);
+
+        let mut path = dispatch.parents.clone();
+        path.push(self.name.clone());
+        let indexed = dispatch
+            .command_index
+            .and_then(|index| index.get(&path)?.get(cmd.as_str()))
+            .and_then(|position| commands.get(*position))
+            .filter(|command| command.matches(cmd));
+        if indexed.is_some() {
+            return indexed;
         }
 
-        if let Some(usage) = &self.usage {
-            text += &format!("Usage:\n\t{}\n\n", usage);
+        // `commands` is public, the sub commands pushed to it or edited after
+        // the app was built, and those of a command run on its own, are only
+        // found by scanning
+        commands.iter().find(|command| command.matches(cmd))
+    }
+
+    /// Flags of the command and of its sub commands, down the tree
+    pub(crate) fn tree_flags(&self) -> Vec<&Flag> {
+        let mut flags: Vec<&Flag> = self.flags.iter().flatten().collect();
+        for command in self.commands.iter().flatten() {
+            flags.extend(command.tree_flags());
         }
+        flags
+    }
 
-        text
+    /// Whether `cmd` is the name or an alias of the command
+    pub(crate) fn matches(&self, cmd: &str) -> bool {
+        match &self.alias {
+            Some(alias) => self.name == cmd || alias.iter().any(|a| a == cmd),
+            None => self.name == cmd,
+        }
+    }
+}
+
+impl Help for Command {
+    fn help_text(&self) -> String {
+        let help_flags: Vec<String> = DEFAULT_HELP_FLAGS.iter().map(|f| f.to_string()).collect();
+        self.help_text_with_usage(self.effective_usage().as_deref(), &[], &help_flags, false)
     }
 }
@@ -0,0 +1,118 @@
+use crate::{App, Command, Flag, FlagType};
+use std::slice;
+
+/// Every token a flag can be typed as, canonical name first,
+/// then its aliases and the `--no-` form of bool flags
+fn flag_words(flags: &[Flag]) -> Vec<String> {
+    flags
+        .iter()
+        .flat_map(|f| {
+            let mut words = vec![f.token()];
+            words.append(&mut f.alias_tokens());
+            if f.flag_type == FlagType::Bool {
+                words.push(format!("--no-{}", f.name));
+            }
+            words
+        })
+        .collect()
+}
+
+/// Names and aliases of `commands`
+fn command_words(commands: &[Command]) -> Vec<String> {
+    commands
+        .iter()
+        .flat_map(|c| {
+            let mut names = vec![c.name.clone()];
+            names.extend(c.alias.iter().flatten().cloned());
+            names
+        })
+        .collect()
+}
+
+/// Add the cases of the commands below `path`: the transitions from `path` to each
+/// command, typed by name or alias, and the words completed at each command path
+fn command_cases(
+    commands: &[Command],
+    path: &str,
+    common_words: &[String],
+    transitions: &mut String,
+    cases: &mut String,
+) {
+    for c in commands {
+        let child = if path.is_empty() {
+            c.name.clone()
+        } else {
+            format!("{} {}", path, c.name)
+        };
+
+        let patterns: Vec<String> = command_words(slice::from_ref(c))
+            .iter()
+            .map(|name| format!("\"{}:{}\"", path, name))
+            .collect();
+        *transitions += &format!(
+            "            {}) path=\"{}\" ;;\n",
+            patterns.join("|"),
+            child
+        );
+
+        let sub_commands = c.commands.as_deref().unwrap_or(&[]);
+        let mut words = command_words(sub_commands);
+        words.extend(flag_words(c.flags.as_deref().unwrap_or(&[])));
+        words.extend(common_words.iter().cloned());
+        *cases += &format!(
+            "        \"{}\")\n            words=\"{}\"\n            ;;\n",
+            child,
+            words.join(" ")
+        );
+
+        command_cases(sub_commands, &child, common_words, transitions, cases);
+    }
+}
+
+/// Bash completion script completing the commands, nested commands and aliases
+/// of `app`, then the flags of the command path typed so far with the global flags
+pub(crate) fn bash(app: &App, bin_name: &str) -> String {
+    let function_name: String = bin_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    // global flags, help and version are accepted at every command path
+    let mut common_words = flag_words(app.global_flags.as_deref().unwrap_or(&[]));
+    common_words.extend(app.help_flags.iter().cloned());
+    if app.version.is_some() {
+        common_words.extend(["-V".to_string(), "--version".to_string()]);
+    }
+
+    let mut transitions = String::new();
+    let mut cases = String::new();
+    command_cases(&app.commands, "", &common_words, &mut transitions, &mut cases);
+
+    let mut app_words = command_words(&app.commands);
+    app_words.extend(flag_words(app.flags.as_deref().unwrap_or(&[])));
+    app_words.extend(common_words);
+    let app_words = app_words.join(" ");
+
+    format!(
+        r#"_{function_name}() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local path="" words i
+
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${{path}}:${{COMP_WORDS[i]}}" in
+{transitions}        esac
+    done
+
+    case "${{path}}" in
+{cases}        *)
+            words="{app_words}"
+            ;;
+    esac
+
+    COMPREPLY=($(compgen -W "${{words}}" -- "${{cur}}"))
+}}
+
+complete -F _{function_name} {bin_name}
+"#
+    )
+}
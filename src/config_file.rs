@@ -0,0 +1,59 @@
+use std::env;
+use toml::{Table, Value};
+
+/// `path` with a leading `~/` replaced by the home directory, when known
+pub(crate) fn expanded_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{}/{}", home.trim_end_matches('/'), rest),
+        _ => path.to_string(),
+    }
+}
+
+/// Parse a TOML config file into raw flag values, keyed by their path of
+/// table names and key, such as `["build", "jobs"]`. Strings, integers, floats
+/// and booleans are supported, failing with the key of anything else.
+pub(crate) fn config_values(content: &str) -> Result<Vec<(Vec<String>, String)>, String> {
+    let table: Table = content.parse().map_err(|e: toml::de::Error| {
+        let message = e.message().trim_end().to_string();
+        match e.span() {
+            Some(span) => {
+                let line = content[..span.start].matches('\n').count() + 1;
+                format!("line {}: {}", line, message)
+            }
+            None => message,
+        }
+    })?;
+
+    let mut values = vec![];
+    table_values(&table, &mut vec![], &mut values)?;
+    Ok(values)
+}
+
+/// Push the raw values of `table`, nested under `path`, to `values`
+fn table_values(
+    table: &Table,
+    path: &mut Vec<String>,
+    values: &mut Vec<(Vec<String>, String)>,
+) -> Result<(), String> {
+    for (key, value) in table {
+        path.push(key.to_owned());
+        let raw = match value {
+            Value::Table(table) => {
+                table_values(table, path, values)?;
+                None
+            }
+            Value::String(text) => Some(text.to_owned()),
+            Value::Integer(number) => Some(number.to_string()),
+            Value::Float(number) => Some(number.to_string()),
+            Value::Boolean(value) => Some(value.to_string()),
+            Value::Array(_) | Value::Datetime(_) => {
+                return Err(format!("unsupported value `{}` for key `{}`", value, path.join(".")));
+            }
+        };
+        if let Some(raw) = raw {
+            values.push((path.clone(), raw));
+        }
+        path.pop();
+    }
+    Ok(())
+}
@@ -0,0 +1,924 @@
+use crate::command::Dispatch;
+use crate::error::{ActionError, ActionErrorKind, ExitCode};
+use crate::flag::parse_bool;
+use crate::parse::{
+    bool_assignments, expanded_abbreviations, expanded_short_flags, split_args,
+    windows_style_flags,
+};
+use crate::{Flag, FlagSource, FlagType};
+use std::cell::{OnceCell, RefCell};
+use std::error::Error;
+use std::io::{self, IsTerminal, Read};
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// Reader given in place of the process stdin, such as in tests
+pub(crate) type StdinReader = Rc<RefCell<dyn Read>>;
+
+/// `error` pointing at the token at `index` of the args when it is a `Parse` error
+fn at_index(mut error: ActionError, index: Option<usize>) -> ActionError {
+    if let ActionErrorKind::Parse { index: ref mut at, .. } = error.kind {
+        *at = index;
+    }
+    error
+}
+
+/// Arguments and resolved flags passed to an action
+///
+/// Example
+///
+/// ```
+/// use suihorse::{Command, Flag, FlagType};
+///
+/// let command = Command::new("cp")
+///     .flag(Flag::new("force", FlagType::Bool).alias("f"))
+///     .flag(Flag::new("mode", FlagType::String))
+///     .action(|c| {
+///         assert_eq!(c.args, vec!["src", "--unknown", "dest", "-f"]);
+///         assert_eq!(c.raw_args, vec!["-f", "src", "--mode", "644", "--unknown", "dest", "-f"]);
+///     });
+///
+/// let args = vec!["-f", "src", "--mode=644", "--unknown", "dest", "--", "-f"];
+/// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+/// ```
+///
+/// Clustered short flags are expanded when each of them is a bool flag
+///
+/// ```
+/// use suihorse::{Command, Flag, FlagType};
+///
+/// let command = Command::new("cmd")
+///     .flag(Flag::new("all", FlagType::Bool).alias("a"))
+///     .flag(Flag::new("brief", FlagType::Bool).alias("b"))
+///     .flag(Flag::new("color", FlagType::Bool).alias("c"))
+///     .flag(Flag::new("output", FlagType::String).alias("o"))
+///     .action(|c| {
+///         assert!(c.bool_flag("all") && c.bool_flag("brief") && c.bool_flag("color"));
+///         assert_eq!(c.args, vec!["-ao", "file", "-xyz"]);
+///     });
+///
+/// let args = vec!["-abc", "-ao", "file", "-xyz"];
+/// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+/// ```
+///
+/// Actions get the canonical name of their command, the app action gets
+/// the first arg when it matched no command
+///
+/// ```
+/// use suihorse::{App, Command};
+///
+/// let app = App::new("cli")
+///     .command(Command::new("build").alias("b").action(|c| {
+///         assert_eq!(c.command_name.as_deref(), Some("build"));
+///     }))
+///     .action(|c| assert_eq!(c.command_name.as_deref(), Some("deploy")));
+///
+/// let args = vec!["cli", "b"];
+/// app.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+/// let args = vec!["cli", "deploy", "prod"];
+/// app.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+/// ```
+///
+/// Actions of nested commands get the path of command names leading to them
+///
+/// ```
+/// use suihorse::{App, Command, Flag, FlagType};
+///
+/// let app = App::new("cli").command(Command::new("remote").command(
+///     Command::new("add")
+///         .flag(Flag::new("force", FlagType::Bool))
+///         .action(|c| {
+///             assert_eq!(c.command_path, vec!["remote", "add"]);
+///             assert!(c.bool_flag("force"));
+///         }),
+/// ));
+///
+/// app.run_slice(&["cli", "remote", "add", "--force", "origin"]).unwrap();
+/// ```
+pub struct Context {
+    /// Positional args, with declared flags and their values removed.
+    /// Unknown `--flags` are kept so they are not silently lost, unless
+    /// `App::deny_unknown_flags` rejects them, and every
+    /// arg after a standalone `--` is kept verbatim.
+    pub args: Vec<String>,
+    /// Every arg given to the command after normalization,
+    /// with clustered short bool flags expanded
+    pub raw_args: Vec<String>,
+    /// Name of the matched command, or the first arg when the app action
+    /// runs because it matched no command
+    pub command_name: Option<String>,
+    /// Names of the matched commands from the app down to the running one,
+    /// empty for the app action
+    pub command_path: Vec<String>,
+    /// Flag names with their raw value and source, if resolved
+    flags: Vec<(String, Option<(String, FlagSource)>)>,
+    /// Flag names with the index in `raw_args` of their value given on the command line
+    cli_indices: Vec<(String, usize)>,
+    /// Normalized args of the command, before any flag parsing
+    rest: Vec<String>,
+    /// Map flag names with their `KEY=VALUE` pairs in command line order
+    maps: Vec<(String, Vec<(String, String)>)>,
+    /// Bool flag names with their number of occurrences on the command line
+    counts: Vec<(String, u64)>,
+    /// Program name as invoked, `argv[0]`
+    program_name: String,
+    /// Help text of the command or app owning the action
+    help_text: String,
+    /// Names of the leading positional args
+    arg_names: Vec<String>,
+    /// Name of the slot capturing the positional args after the named ones
+    variadic_name: Option<String>,
+    /// Reader of `stdin`, the process stdin when None
+    stdin_reader: Option<StdinReader>,
+    /// Content of stdin, read on the first call to `stdin`
+    stdin: OnceCell<String>,
+}
+
+impl Context {
+    /// Create new instance of `Context`
+    /// Parse the flags of `args` before a standalone `--`,
+    /// failing on the first resolved value its flag rejects,
+    /// then on the first required flag without value, with `usage` in the error.
+    /// `command_path` scopes the config file values.
+    pub(crate) fn new(
+        dispatch: &Dispatch,
+        command_name: Option<&str>,
+        command_path: Vec<String>,
+        args: &[String],
+        flags: &Option<Vec<Flag>>,
+        help_text: String,
+        usage: Option<&str>,
+    ) -> Result<Self, ActionError> {
+        let declared: Vec<&Flag> = flags.iter().flatten().chain(dispatch.global_flags).collect();
+        let rest = args.to_vec();
+        let (mut flag_args, trailing) = split_args(args, &declared);
+        if dispatch.windows_flags {
+            flag_args = windows_style_flags(flag_args, &declared);
+        }
+        if dispatch.abbreviate_flags {
+            flag_args = expanded_abbreviations(flag_args, &declared)?;
+        }
+        let flag_args = expanded_short_flags(flag_args, &declared);
+        let flag_args = bool_assignments(&flag_args, &declared)?;
+
+        let mut positionals = vec![];
+        // args without the values of flags, so a value like `-5` never sets a bool flag
+        let mut without_values = vec![];
+        // every value of map flags, in order, where other flags resolve to their last one
+        let mut map_values: Vec<(&str, &str, usize)> = vec![];
+        // index of every value in `flag_args`, the last one per flag is resolved
+        let mut value_indices: Vec<(&str, usize)> = vec![];
+        let mut unknown_flags = vec![];
+        let mut iter = flag_args.iter().enumerate();
+        while let Some((_, arg)) = iter.next() {
+            match declared.iter().find(|f| f.matches(arg)) {
+                Some(f) if f.takes_value() => match iter.next() {
+                    Some((index, value)) => {
+                        value_indices.push((&f.name, index));
+                        if f.flag_type == FlagType::Map {
+                            map_values.push((&f.name, value, index));
+                        }
+                    }
+                    None => {
+                        return Err(ActionError {
+                            kind: ActionErrorKind::MissingValue(f.name.clone()),
+                        })
+                    }
+                },
+                Some(_) => {}
+                None => {
+                    if arg.starts_with("--") && !dispatch.help_flags.contains(arg) {
+                        unknown_flags.push(arg.to_owned());
+                    }
+                    positionals.push(arg.to_owned())
+                }
+            }
+            without_values.push(arg.to_owned());
+        }
+        if dispatch.deny_unknown_flags && !unknown_flags.is_empty() {
+            return Err(ActionError {
+                kind: ActionErrorKind::UnknownFlags(unknown_flags),
+            });
+        }
+        positionals.extend(trailing.iter().cloned());
+
+        let mut resolved = vec![];
+        let mut cli_indices = vec![];
+        for f in declared.iter() {
+            let value = if f.takes_value() {
+                f.resolve(&flag_args, dispatch, &command_path)
+            } else {
+                f.resolve(&without_values, dispatch, &command_path)
+            };
+            let index = value_indices
+                .iter()
+                .rev()
+                .find(|(name, _)| *name == f.name)
+                .map(|(_, index)| *index)
+                .filter(|_| matches!(value, Some((_, FlagSource::Cli))));
+            if let Some((value, _)) = &value {
+                f.validate(value).map_err(|e| at_index(e, index))?;
+            }
+            cli_indices.extend(index.map(|index| (f.name.clone(), index)));
+            resolved.push((f.name.clone(), value));
+        }
+        let missing = declared
+            .iter()
+            .zip(resolved.iter())
+            .find(|(f, (_, value))| f.required && value.is_none());
+        if let Some((f, _)) = missing {
+            return Err(ActionError {
+                kind: ActionErrorKind::MissingRequiredFlag {
+                    flag: f.name.clone(),
+                    usage: usage.map(String::from),
+                },
+            });
+        }
+
+        let counts = declared
+            .iter()
+            .filter(|f| f.flag_type == FlagType::Bool)
+            .map(|f| (f.name.clone(), f.occurrences(&without_values)))
+            .collect();
+
+        let mut maps = vec![];
+        for f in declared.iter().filter(|f| f.flag_type == FlagType::Map) {
+            let mut values: Vec<(&str, Option<usize>)> = map_values
+                .iter()
+                .filter(|(name, _, _)| *name == f.name)
+                .map(|(_, value, index)| (*value, Some(*index)))
+                .collect();
+            if values.is_empty() {
+                let resolved_value = resolved
+                    .iter()
+                    .find(|(name, _)| *name == f.name)
+                    .and_then(|(_, value)| value.as_ref());
+                values.extend(resolved_value.map(|(value, _)| (value.as_str(), None)));
+            }
+
+            let mut pairs = vec![];
+            for (value, index) in values {
+                f.validate(value).map_err(|e| at_index(e, index))?;
+                if let Some((key, value)) = value.split_once('=') {
+                    pairs.push((key.to_string(), value.to_string()));
+                }
+            }
+            maps.push((f.name.clone(), pairs));
+        }
+
+        let mut raw_args = flag_args;
+        raw_args.extend(trailing);
+
+        Ok(Self {
+            args: positionals,
+            raw_args,
+            rest,
+            flags: resolved,
+            cli_indices,
+            maps,
+            counts,
+            command_name: command_name.map(String::from),
+            command_path,
+            program_name: dispatch.program_name.to_string(),
+            help_text,
+            arg_names: vec![],
+            variadic_name: None,
+            stdin_reader: dispatch.stdin.clone(),
+            stdin: OnceCell::new(),
+        })
+    }
+
+    /// Name the leading positional args and the slot capturing the rest
+    pub(crate) fn bind_args(&mut self, arg_names: &[String], variadic_name: Option<&str>) {
+        self.arg_names = arg_names.to_vec();
+        self.variadic_name = variadic_name.map(String::from);
+    }
+
+    /// Get the args following the matched command name, the pre-parse view
+    /// for custom parsing: flags are neither split at `=` nor removed and `--`
+    /// is kept. The args are already trimmed, with empty ones dropped, before
+    /// `--`, and `@path` args expanded with `App::response_files`. Global flags
+    /// given before the command name come first, with their value.
+    /// The app action gets every arg after the program name.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command, Flag, FlagType};
+    ///
+    /// let app = App::new("cli")
+    ///     .global_flag(Flag::new("profile", FlagType::String))
+    ///     .command(
+    ///         Command::new("build")
+    ///             .flag(Flag::new("x", FlagType::Bool))
+    ///             .action(|c| {
+    ///                 assert_eq!(c.rest(), ["--profile", "dev", "--x", "a", "b"]);
+    ///                 assert_eq!(c.args, vec!["a", "b"]);
+    ///             }),
+    ///     );
+    ///
+    /// app.run_slice(&["myapp", "--profile", "dev", "build", " --x ", "", "a", "b"]).unwrap();
+    /// ```
+    pub fn rest(&self) -> &[String] {
+        &self.rest
+    }
+
+    /// Get the positional arg declared `name` with `Command::arg`, None when not declared
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::Command;
+    ///
+    /// let command = Command::new("mv")
+    ///     .arg("src")
+    ///     .arg("dest")
+    ///     .action(|c| assert_eq!((c.arg("src"), c.arg("dest")), (Some("a"), Some("b"))));
+    ///
+    /// command.run_with_result(vec!["a".to_string(), "b".to_string()]).unwrap();
+    /// ```
+    pub fn arg(&self, name: &str) -> Option<&str> {
+        let index = self.arg_names.iter().position(|arg_name| arg_name == name)?;
+        self.args.get(index).map(|arg| arg.as_str())
+    }
+
+    /// Get the positional args after the named ones when `name` is the slot
+    /// declared with `Command::variadic_arg`, empty otherwise
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::Command;
+    ///
+    /// let command = Command::new("exec")
+    ///     .arg("cmd")
+    ///     .variadic_arg("rest")
+    ///     .action(|c| {
+    ///         assert_eq!(c.variadic("rest"), ["a", "b"]);
+    ///         assert!(c.variadic("other").is_empty());
+    ///     });
+    ///
+    /// let args = vec!["echo", "a", "b"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// ```
+    pub fn variadic(&self, name: &str) -> &[String] {
+        match self.variadic_name.as_deref() {
+            Some(variadic_name) if variadic_name == name => {
+                self.args.get(self.arg_names.len()..).unwrap_or(&[])
+            }
+            _ => &[],
+        }
+    }
+
+    /// Get the program name as invoked (`argv[0]`), which may differ from the app
+    /// name when the binary is symlinked. Empty when a command runs outside of `App`.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let command = Command::new("build")
+    ///     .action(|c| assert_eq!(c.program_name(), "/usr/local/bin/mytool"));
+    /// let app = App::new("cli")
+    ///     .usage("cli [command] [arg]")
+    ///     .command(command);
+    ///
+    /// let args = vec!["/usr/local/bin/mytool", "build"];
+    /// app.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// ```
+    pub fn program_name(&self) -> &str {
+        &self.program_name
+    }
+
+    /// Get the help text of the command owning the action,
+    /// or of the app when called from the app action
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let app = App::new("cli")
+    ///     .description("my tool")
+    ///     .command(Command::new("build").description("build the package").action(|c| {
+    ///         assert!(c.help_text().contains("build the package"));
+    ///     }))
+    ///     .action(|c| assert!(c.help_text().contains("my tool")));
+    ///
+    /// let args = vec!["cli", "build"];
+    /// app.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// let args = vec!["cli"];
+    /// app.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// ```
+    pub fn help_text(&self) -> &str {
+        &self.help_text
+    }
+
+    /// Get the whole stdin, read on the first call and buffered for the next ones.
+    /// A terminal stdin is not read, so this never waits for the user to type,
+    /// it is empty then, as is stdin failing to read as UTF-8.
+    /// See `Command::terminal_stdin_error` to fail instead.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::Command;
+    ///
+    /// let command = Command::new("wc")
+    ///     .reads_stdin(true)
+    ///     .action(|c| {
+    ///         assert_eq!(c.stdin().lines().count(), 2);
+    ///         assert_eq!(c.stdin(), "one\ntwo\n");
+    ///     });
+    ///
+    /// let input: &[u8] = b"one\ntwo\n";
+    /// command.run_with_stdin(vec![], input).unwrap();
+    /// ```
+    pub fn stdin(&self) -> &str {
+        self.stdin.get_or_init(|| {
+            let mut content = String::new();
+            match &self.stdin_reader {
+                Some(reader) => {
+                    let _ = reader.borrow_mut().read_to_string(&mut content);
+                }
+                None => {
+                    let stdin = io::stdin();
+                    if !stdin.is_terminal() {
+                        let _ = stdin.lock().read_to_string(&mut content);
+                    }
+                }
+            }
+            content
+        })
+    }
+
+    /// Print the help of the command owning the action, such as on bad input.
+    /// The app action prints the app help.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::Command;
+    ///
+    /// let command = Command::new("cp")
+    ///     .usage("cli cp <src> <dest>")
+    ///     .action(|c| {
+    ///         if c.args.len() != 2 {
+    ///             c.help();
+    ///         }
+    ///     });
+    ///
+    /// command.run_with_result(vec!["a.txt".to_string()]).unwrap();
+    /// ```
+    pub fn help(&self) {
+        println!("{}", self.help_text);
+    }
+
+    /// Error to return from an action to exit the process with `code`,
+    /// see `ExitCode`
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::App;
+    ///
+    /// let app = App::new("cli")
+    ///     .action_with_result(|c| if c.args == ["check"] { c.exit_with(3) } else { Ok(()) });
+    ///
+    /// let args = vec!["cli", "check"];
+    /// assert_eq!(app.run_and_exit_code(args.into_iter().map(String::from).collect()), 3);
+    /// ```
+    pub fn exit_with(&self, code: i32) -> Result<(), Box<dyn Error>> {
+        Err(Box::new(ExitCode(code)))
+    }
+
+    /// Whether `--dry-run` was given, sugar over `bool_flag("dry-run")`
+    /// for the flag declared with `App::global_bool_flag("dry-run")`
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{App, Command};
+    ///
+    /// let app = App::new("cli")
+    ///     .command(Command::new("deploy").action(|c| assert!(c.is_dry_run())))
+    ///     .global_bool_flag("dry-run");
+    ///
+    /// let args = vec!["cli", "deploy", "--dry-run"];
+    /// app.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// ```
+    pub fn is_dry_run(&self) -> bool {
+        self.bool_flag("dry-run")
+    }
+
+    /// Get bool flag, false when unset
+    ///
+    /// `--no-<name>` sets a bool flag to false and the last occurrence wins.
+    /// A flag declared with a default of `"true"` stays true unless
+    /// `--no-<name>` is given. `--<name>=true` and `--<name>=false` set it too,
+    /// any value after `=` that is not a bool fails the run with a `Parse` error.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("cmd")
+    ///     .flag(Flag::new("color", FlagType::Bool))
+    ///     .action(|c| assert!(!c.bool_flag("color")));
+    /// let args = vec!["--color", "--no-color"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    ///
+    /// let command = Command::new("cmd")
+    ///     .flag(Flag::new("color", FlagType::Bool))
+    ///     .action(|c| assert!(c.bool_flag("color")));
+    /// let args = vec!["--no-color", "--color"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    ///
+    /// let command = Command::new("cmd")
+    ///     .flag(Flag::new("color", FlagType::Bool).default_value("true"))
+    ///     .action(|c| assert!(!c.bool_flag("color")));
+    /// let args = vec!["--no-color"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// ```
+    ///
+    /// A value given with `=` is read as the flag value, not as a positional arg
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let seen = Arc::new(Mutex::new(vec![]));
+    /// let log = seen.clone();
+    /// let command = Command::new("cmd")
+    ///     .flag(Flag::new("verbose", FlagType::Bool).alias("v"))
+    ///     .action(move |c| log.lock().unwrap().push((c.bool_flag("verbose"), c.args.clone())));
+    ///
+    /// for args in [vec!["--verbose=false", "file"], vec!["-v=true", "file"]] {
+    ///     command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// }
+    /// assert_eq!(
+    ///     *seen.lock().unwrap(),
+    ///     vec![(false, vec!["file".to_string()]), (true, vec!["file".to_string()])]
+    /// );
+    ///
+    /// let error = command.run_with_result(vec!["--verbose=maybe".to_string()]).unwrap_err();
+    /// assert_eq!(error.to_string(), r#"Parse: invalid value "maybe" for flag --verbose at arg 0 (expected bool)"#);
+    /// ```
+    ///
+    /// A value after `=`, in env or in the config file is `true`, `yes` or `1`,
+    /// or `false`, `no` or `0`, in any case. Any other fails with a `Parse` error.
+    ///
+    /// ```
+    /// use std::env;
+    /// use suihorse::{App, Command, Flag, FlagType};
+    ///
+    /// let app = App::new("cli")
+    ///     .env_prefix("BOOLAPP")
+    ///     .command(
+    ///         Command::new("build")
+    ///             .flag(Flag::new("release", FlagType::Bool).env("BOOLAPP_RELEASE_MODE"))
+    ///             .flag(Flag::new("verbose", FlagType::Bool))
+    ///             .action(|c| assert!(c.bool_flag("release") && !c.bool_flag("verbose"))),
+    ///     );
+    ///
+    /// for (release, verbose) in [("1", "no"), ("YES", "0"), ("True", "FALSE")] {
+    ///     env::set_var("BOOLAPP_RELEASE_MODE", release);
+    ///     env::set_var("BOOLAPP_VERBOSE", verbose);
+    ///     app.run_slice(&["cli", "build"]).unwrap();
+    /// }
+    /// app.run_slice(&["cli", "build", "--verbose=No"]).unwrap();
+    ///
+    /// env::set_var("BOOLAPP_VERBOSE", "maybe");
+    /// let error = app.run_slice(&["cli", "build"]).unwrap_err();
+    /// assert_eq!(error.to_string(), r#"Parse: invalid value "maybe" for flag --verbose (expected bool)"#);
+    /// ```
+    pub fn bool_flag(&self, name: &str) -> bool {
+        match self.flag_raw(name) {
+            Some(value) => parse_bool(value).unwrap_or(false),
+            None => false,
+        }
+    }
+
+    /// Get how many times the bool flag was given on the command line, such as
+    /// a verbosity level, clustered short flags counting one each. It never
+    /// consumes a value. 0 when missing, env and defaults are not counted.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("cmd")
+    ///     .flag(Flag::new("verbose", FlagType::Bool).alias("v"))
+    ///     .flag(Flag::new("quiet", FlagType::Bool).alias("q"))
+    ///     .action(|c| {
+    ///         assert_eq!(c.count_flag("verbose"), 3);
+    ///         assert_eq!(c.count_flag("quiet"), 0);
+    ///         assert_eq!(c.args, vec!["file"]);
+    ///     });
+    ///
+    /// command.run_with_result(vec!["-vvv".to_string(), "file".to_string()]).unwrap();
+    /// let args = vec!["-v", "file", "--verbose", "-v"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    ///
+    /// let command = Command::new("cmd")
+    ///     .flag(Flag::new("verbose", FlagType::Bool).alias("v"))
+    ///     .action(|c| assert_eq!(c.count_flag("verbose"), 2));
+    ///
+    /// command.run_with_result(vec!["-v".to_string(), "-v".to_string()]).unwrap();
+    /// let args = vec!["--verbose", "--verbose"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// ```
+    pub fn count_flag(&self, name: &str) -> u64 {
+        match self.counts.iter().find(|(n, _)| n == name) {
+            Some((_, count)) => *count,
+            None => 0,
+        }
+    }
+
+    /// Whether the flag was given on the command line,
+    /// false when its value comes from env or a default
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("cmd")
+    ///     .flag(Flag::new("count", FlagType::Int).default_value("1"))
+    ///     .flag(Flag::new("jobs", FlagType::Int).default_value("1"))
+    ///     .action(|c| {
+    ///         assert!(!c.flag_present("count"));
+    ///         assert!(c.flag_present("jobs"));
+    ///     });
+    ///
+    /// let args = vec!["--jobs", "4"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// ```
+    pub fn flag_present(&self, name: &str) -> bool {
+        self.flag_source(name) == Some(FlagSource::Cli)
+    }
+
+    /// Names of the flags whose value comes from an env fallback,
+    /// e.g. to warn about secrets passed through env
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// std::env::set_var("MYAPP_TOKEN", "secret");
+    /// std::env::remove_var("MYAPP_NETWORK");
+    ///
+    /// let command = Command::new("cmd")
+    ///     .flag(Flag::new("token", FlagType::String).env("MYAPP_TOKEN"))
+    ///     .flag(Flag::new("network", FlagType::String).env("MYAPP_NETWORK").default_value("testnet"))
+    ///     .flag(Flag::new("package", FlagType::String).env("MYAPP_TOKEN"))
+    ///     .action(|c| assert_eq!(c.env_sourced_flags(), vec!["token"]));
+    ///
+    /// let args = vec!["--package", "0x2"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// ```
+    pub fn env_sourced_flags(&self) -> Vec<&str> {
+        self.flags
+            .iter()
+            .filter(|(_, resolved)| matches!(resolved, Some((_, FlagSource::Env))))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Where the resolved value of a flag comes from, None when unset
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagSource, FlagType};
+    ///
+    /// std::env::set_var("MYAPP_REGION", "eu-west");
+    ///
+    /// let command = Command::new("deploy")
+    ///     .flag(Flag::new("force", FlagType::Bool))
+    ///     .flag(Flag::new("region", FlagType::String).env("MYAPP_REGION"))
+    ///     .flag(Flag::new("replicas", FlagType::Int).default_value("1"))
+    ///     .flag(Flag::new("tag", FlagType::String))
+    ///     .action(|c| {
+    ///         assert_eq!(c.flag_source("force"), Some(FlagSource::Cli));
+    ///         assert_eq!(c.flag_source("region"), Some(FlagSource::Env));
+    ///         assert_eq!(c.flag_source("replicas"), Some(FlagSource::Default));
+    ///         assert_eq!(c.flag_source("tag"), None);
+    ///     });
+    ///
+    /// command.run_with_result(vec!["--force".to_string()]).unwrap();
+    /// ```
+    pub fn flag_source(&self, name: &str) -> Option<FlagSource> {
+        self.flags
+            .iter()
+            .find(|(flag_name, _)| flag_name == name)
+            .and_then(|(_, resolved)| resolved.as_ref())
+            .map(|(_, source)| *source)
+    }
+
+    /// Get the `KEY=VALUE` pairs of a map flag, split on the first `=`, in the order
+    /// given. Empty when unset or not a map flag. A value without `=` fails the run
+    /// with a `Parse` error.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("cc")
+    ///     .flag(Flag::new("define", FlagType::Map).alias("D"))
+    ///     .action(|c| {
+    ///         let pairs: Vec<(&str, &str)> =
+    ///             c.map_flag("define").iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    ///         assert_eq!(pairs, vec![("DEBUG", "1"), ("NAME", "a=b"), ("EMPTY", "")]);
+    ///         assert_eq!(c.args, vec!["main.c"]);
+    ///     });
+    ///
+    /// let args = vec!["-D", "DEBUG=1", "--define=NAME=a=b", "main.c", "-D", "EMPTY="];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    ///
+    /// let error = command.run_with_result(vec!["-D".to_string(), "DEBUG".to_string()]).unwrap_err();
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     r#"Parse: invalid value "DEBUG" for flag --define at arg 1 (expected KEY=VALUE)"#
+    /// );
+    /// ```
+    pub fn map_flag(&self, name: &str) -> &[(String, String)] {
+        self.maps
+            .iter()
+            .find(|(flag_name, _)| flag_name == name)
+            .map_or(&[], |(_, pairs)| pairs.as_slice())
+    }
+
+    /// Get the resolved value of a flag as given, before any type conversion.
+    /// Bool flags resolve to `"true"` or `"false"`.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("cmd")
+    ///     .flag(Flag::new("force", FlagType::Bool))
+    ///     .flag(Flag::new("name", FlagType::String))
+    ///     .flag(Flag::new("jobs", FlagType::Int))
+    ///     .flag(Flag::new("ratio", FlagType::Float))
+    ///     .flag(Flag::new("unset", FlagType::String))
+    ///     .action(|c| {
+    ///         assert_eq!(c.flag_raw("force"), Some("true"));
+    ///         assert_eq!(c.flag_raw("name"), Some("suihorse"));
+    ///         assert_eq!(c.flag_raw("jobs"), Some("08"));
+    ///         assert_eq!(c.flag_raw("ratio"), Some("1.50"));
+    ///         assert_eq!(c.flag_raw("unset"), None);
+    ///     });
+    ///
+    /// let args = vec!["--force", "--name", "suihorse", "--jobs=08", "--ratio", "1.50"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// ```
+    ///
+    /// A value starting with `-` is taken by its flag, not read as another flag
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("seek")
+    ///     .flag(Flag::new("offset", FlagType::Int))
+    ///     .flag(Flag::new("gain", FlagType::Float))
+    ///     .flag(Flag::new("five", FlagType::Bool).alias("5"))
+    ///     .action(|c| {
+    ///         assert_eq!(c.flag_raw("offset"), Some("-5"));
+    ///         assert_eq!(c.flag_raw("gain"), Some("-0.5"));
+    ///         assert!(!c.bool_flag("five"));
+    ///         assert_eq!(c.args, vec!["file"]);
+    ///     });
+    ///
+    /// let args = vec!["--offset", "-5", "--gain=-0.5", "file"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// let args = vec!["--offset=-5", "file", "--gain", "-0.5"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// ```
+    ///
+    /// The last occurrence of a flag wins, as for bool flags
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("build")
+    ///     .flag(Flag::new("jobs", FlagType::Int).alias("j"))
+    ///     .flag(Flag::new("force", FlagType::Bool))
+    ///     .action(|c| {
+    ///         assert_eq!(c.flag_raw("jobs"), Some("2"));
+    ///         assert!(!c.bool_flag("force"));
+    ///     });
+    ///
+    /// let args = vec!["--jobs", "1", "--force", "-j", "2", "--no-force"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// ```
+    pub fn flag_raw(&self, name: &str) -> Option<&str> {
+        self.flags
+            .iter()
+            .find(|(flag_name, _)| flag_name == name)
+            .and_then(|(_, resolved)| resolved.as_ref())
+            .map(|(value, _)| value.as_str())
+    }
+
+    /// Get the value of the flag as a string, failing with a `MissingFlag` error
+    /// when it is neither on the command line, in env nor defaulted
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::error::{ActionError, ActionErrorKind};
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("greet")
+    ///     .flag(Flag::new("name", FlagType::String))
+    ///     .action_with_result(|c| {
+    ///         println!("Hello, {}", c.string_flag("name")?);
+    ///         Ok(())
+    ///     });
+    ///
+    /// let args = vec!["--name", "ann"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// let error = command.run_with_result(vec![]).unwrap_err();
+    /// assert_eq!(
+    ///     error.downcast_ref::<ActionError>().unwrap().kind,
+    ///     ActionErrorKind::MissingFlag("name".to_string())
+    /// );
+    /// ```
+    pub fn string_flag(&self, name: &str) -> Result<String, ActionError> {
+        self.flag_raw(name).map(String::from).ok_or_else(|| ActionError {
+            kind: ActionErrorKind::MissingFlag(name.to_string()),
+        })
+    }
+
+    /// Get the value of the flag as an integer, failing with a `MissingFlag` error
+    /// when it has no value or a `Parse` error when the value is not an integer
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("build")
+    ///     .flag(Flag::new("jobs", FlagType::Int).default_value("4"))
+    ///     .flag(Flag::new("name", FlagType::String))
+    ///     .action_with_result(|c| {
+    ///         assert_eq!(c.int_flag("jobs")?, 4);
+    ///         c.int_flag("name")?;
+    ///         Ok(())
+    ///     });
+    ///
+    /// let args = vec!["--name", "app"];
+    /// let error = command.run_with_result(args.into_iter().map(String::from).collect()).unwrap_err();
+    /// assert_eq!(error.to_string(), r#"Parse: invalid value "app" for flag --name at arg 1 (expected integer)"#);
+    /// ```
+    pub fn int_flag(&self, name: &str) -> Result<i64, ActionError> {
+        self.parsed_flag(name, "integer")
+    }
+
+    /// Get the value of the flag as a number, failing with a `MissingFlag` error
+    /// when it has no value or a `Parse` error when the value is not a number
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("scale")
+    ///     .flag(Flag::new("ratio", FlagType::Float))
+    ///     .action_with_result(|c| {
+    ///         assert_eq!(c.float_flag("ratio")?, 1.5);
+    ///         Ok(())
+    ///     });
+    ///
+    /// let args = vec!["--ratio", "1.5"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// assert!(command.run_with_result(vec![]).is_err());
+    /// ```
+    pub fn float_flag(&self, name: &str) -> Result<f64, ActionError> {
+        self.parsed_flag(name, "number")
+    }
+
+    /// Value of the flag parsed as `T`, `expected` naming `T` in the `Parse` error
+    fn parsed_flag<T: FromStr>(&self, name: &str, expected: &str) -> Result<T, ActionError> {
+        let value = self.string_flag(name)?;
+        let index = self
+            .cli_indices
+            .iter()
+            .find(|(flag_name, _)| flag_name == name)
+            .map(|(_, index)| *index);
+        value.parse().map_err(|_| ActionError {
+            kind: ActionErrorKind::Parse {
+                flag: name.to_string(),
+                value,
+                expected: expected.to_string(),
+                index,
+            },
+        })
+    }
+}
@@ -13,15 +13,229 @@ impl fmt::Display for ActionError {
 
 impl std::error::Error for ActionError {}
 
+/// Error returned by an action to exit the process with this code,
+/// used by `App::run` and `App::run_and_exit_code` over `error_exit_code`
+///
+/// Example
+///
+/// ```
+/// use suihorse::error::ExitCode;
+/// use suihorse::App;
+///
+/// let app = App::new("cli")
+///     .action_with_result(|c| {
+///         if !c.args.iter().any(|arg| arg == "needle") {
+///             return Err(Box::new(ExitCode(1)));
+///         }
+///         Ok(())
+///     })
+///     .error_exit_code(2);
+///
+/// let args = vec!["cli", "hay", "needle"];
+/// assert_eq!(app.run_and_exit_code(args.into_iter().map(String::from).collect()), 0);
+/// let args = vec!["cli", "hay"];
+/// assert_eq!(app.run_and_exit_code(args.into_iter().map(String::from).collect()), 1);
+/// ```
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct ExitCode(pub i32);
+
+impl fmt::Display for ExitCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ExitCode: {}", self.0)
+    }
+}
+
+impl std::error::Error for ExitCode {}
+
+/// What went wrong
+///
+/// Example
+///
+/// ```
+/// use suihorse::error::{ActionError, ActionErrorKind};
+/// use suihorse::{Command, Flag, FlagType};
+///
+/// let command = Command::new("seek")
+///     .flag(Flag::new("ratio", FlagType::Float))
+///     .action(|_| unreachable!());
+///
+/// let error = command.run_with_result(vec!["--ratio=half".to_string()]).unwrap_err();
+/// assert_eq!(
+///     error.downcast_ref::<ActionError>().unwrap().kind,
+///     ActionErrorKind::Parse {
+///         flag: "ratio".to_string(),
+///         value: "half".to_string(),
+///         expected: "number".to_string(),
+///         index: Some(1),
+///     }
+/// );
+/// assert_eq!(
+///     error.to_string(),
+///     r#"Parse: invalid value "half" for flag --ratio at arg 1 (expected number)"#
+/// );
+///
+/// let error = command.run_with_result(vec!["0.5".to_string(), "--ratio".to_string()]).unwrap_err();
+/// assert_eq!(
+///     error.downcast_ref::<ActionError>().unwrap().kind,
+///     ActionErrorKind::MissingValue("ratio".to_string())
+/// );
+/// assert_eq!(error.to_string(), "MissingValue: --ratio expects a value");
+///
+/// // a value that isn't on the command line has no index
+/// let command = Command::new("seek")
+///     .flag(Flag::new("ratio", FlagType::Float).default_value("half"))
+///     .action(|_| unreachable!());
+/// let error = command.run_with_result(vec!["file".to_string()]).unwrap_err();
+/// assert_eq!(error.to_string(), r#"Parse: invalid value "half" for flag --ratio (expected number)"#);
+/// ```
 #[derive(PartialEq, Clone, Debug)]
 pub enum ActionErrorKind {
     NotFound,
+    /// Arg at this index of the args is not valid UTF-8, lossily converted
+    InvalidUtf8(usize, String),
+    /// Prefix matching more than one command, with their names
+    Ambiguous(String, Vec<String>),
+    /// Token matching no command, as typed
+    UnknownCommand(String),
+    /// Value of the named flag rejected by its type or validator, with the reason
+    InvalidFlagValue(String, String),
+    /// Positional args not matching what the action expects, with the reason
+    InvalidArgs(String),
+    /// Command name registered twice
+    Duplicate(String),
+    /// Command registered without a name
+    EmptyName,
+    /// Name or alias of a new command, with the command, already the name or
+    /// alias of the registered command
+    AliasCollision(String, String, String),
+    /// Action panicked while `App::catch_unwind` was on, with the panic message
+    Panicked(String),
+    /// Response file given as `@path` that could not be read, with the reason
+    ResponseFile(String, String),
+    /// Two flags of a `Command::conflicts` group given together
+    Conflict(String, String),
+    /// Positional args given to the named command, which has no action
+    NoAction(String),
+    /// Long flag prefix matching more than one flag, with their tokens
+    AmbiguousFlag(String, Vec<String>),
+    /// Raw value of a flag not parsing as its type, with the expected type
+    /// and, when given on the command line, the index of the offending token
+    /// in `Context::raw_args`
+    Parse {
+        flag: String,
+        value: String,
+        expected: String,
+        index: Option<usize>,
+    },
+    /// Flag value outside `Flag::possible_values`, with the flag, value and choices
+    InvalidChoice(String, String, Vec<String>),
+    /// Long flags matching no declared flag, with `App::deny_unknown_flags` on
+    UnknownFlags(Vec<String>),
+    /// Command reading stdin run from a terminal, with `Command::terminal_stdin_error` on
+    TerminalStdin(String),
+    /// Flag taking a value given last, or right before `--`, without one
+    MissingValue(String),
+    /// Flag read with a typed getter of `Context` without a value
+    MissingFlag(String),
+    /// Config file with its path and why it couldn't be read
+    ConfigFile(String, String),
+    /// Required flag resolved to no value, with the usage of its command
+    MissingRequiredFlag {
+        flag: String,
+        usage: Option<String>,
+    },
+    /// App without action given no command, with `App::no_command_error` on
+    NoCommand,
 }
 
 impl fmt::Display for ActionErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             ActionErrorKind::NotFound => f.write_str("NotFound"),
+            ActionErrorKind::InvalidUtf8(index, ref arg) => {
+                write!(f, "InvalidUtf8: argument {} ({:?}) is not valid UTF-8", index, arg)
+            }
+            ActionErrorKind::Ambiguous(ref prefix, ref names) => write!(
+                f,
+                r#"Ambiguous: "{}" matches commands {}"#,
+                prefix,
+                names.join(", ")
+            ),
+            ActionErrorKind::UnknownCommand(ref cmd) => {
+                write!(f, r#"UnknownCommand: "{}" is not a command"#, cmd)
+            }
+            ActionErrorKind::InvalidFlagValue(ref flag, ref message) => {
+                write!(f, "InvalidFlagValue: --{}: {}", flag, message)
+            }
+            ActionErrorKind::InvalidArgs(ref message) => write!(f, "InvalidArgs: {}", message),
+            ActionErrorKind::Duplicate(ref name) => {
+                write!(f, r#"Duplicate: command "{}" is already registered"#, name)
+            }
+            ActionErrorKind::EmptyName => f.write_str("EmptyName: command name must not be empty"),
+            ActionErrorKind::AliasCollision(ref command, ref name, ref registered) => write!(
+                f,
+                r#"AliasCollision: "{}" of command "{}" is already used by command "{}""#,
+                name, command, registered
+            ),
+            ActionErrorKind::Panicked(ref message) => write!(f, "Panicked: {}", message),
+            ActionErrorKind::ResponseFile(ref path, ref reason) => {
+                write!(f, "ResponseFile: @{}: {}", path, reason)
+            }
+            ActionErrorKind::Conflict(ref first, ref second) => {
+                write!(f, "Conflict: --{} cannot be used with --{}", first, second)
+            }
+            ActionErrorKind::NoAction(ref name) => {
+                write!(f, r#"NoAction: command "{}" has no action to run"#, name)
+            }
+            ActionErrorKind::AmbiguousFlag(ref prefix, ref tokens) => write!(
+                f,
+                r#"AmbiguousFlag: "{}" matches flags {}"#,
+                prefix,
+                tokens.join(", ")
+            ),
+            ActionErrorKind::Parse {
+                ref flag,
+                ref value,
+                ref expected,
+                index,
+            } => {
+                write!(f, r#"Parse: invalid value "{}" for flag --{}"#, value, flag)?;
+                if let Some(index) = index {
+                    write!(f, " at arg {}", index)?;
+                }
+                write!(f, " (expected {})", expected)
+            }
+            ActionErrorKind::InvalidChoice(ref flag, ref value, ref values) => write!(
+                f,
+                r#"InvalidChoice: "{}" isn't a valid value for --{} [possible values: {}]"#,
+                value,
+                flag,
+                values.join(", ")
+            ),
+            ActionErrorKind::UnknownFlags(ref tokens) => {
+                write!(f, "UnknownFlags: {}", tokens.join(", "))
+            }
+            ActionErrorKind::TerminalStdin(ref name) => {
+                write!(f, r#"TerminalStdin: command "{}" reads stdin, which is a terminal"#, name)
+            }
+            ActionErrorKind::MissingValue(ref flag) => {
+                write!(f, "MissingValue: --{} expects a value", flag)
+            }
+            ActionErrorKind::MissingFlag(ref flag) => write!(f, "MissingFlag: --{} has no value", flag),
+            ActionErrorKind::ConfigFile(ref path, ref reason) => {
+                write!(f, "ConfigFile: {}: {}", path, reason)
+            }
+            ActionErrorKind::MissingRequiredFlag {
+                ref flag,
+                ref usage,
+            } => {
+                write!(f, "MissingRequiredFlag: --{} is required", flag)?;
+                match usage {
+                    Some(usage) => write!(f, "\nUsage: {}", usage),
+                    None => Ok(()),
+                }
+            }
+            ActionErrorKind::NoCommand => f.write_str("NoCommand: no command specified"),
         }
     }
 }
\ No newline at end of file
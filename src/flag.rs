@@ -0,0 +1,530 @@
+use crate::command::Dispatch;
+use crate::error::{ActionError, ActionErrorKind};
+use std::sync::Arc;
+use std::{env, fmt};
+
+/// Check of a raw flag value, returning why it is rejected,
+/// `Send` and `Sync` like `Action`
+pub type Validator = Arc<dyn Fn(&str) -> Result<(), String> + Send + Sync>;
+
+/// Raw value of a bool flag, `true`, `yes` or `1` and `false`, `no` or `0`
+/// in any case
+pub(crate) fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "1" => Some(true),
+        "false" | "no" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Command and application flag type
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Flag {
+    /// Flag name
+    pub name: String,
+    /// Flag description
+    pub description: Option<String>,
+    /// Flag type
+    pub flag_type: FlagType,
+    /// Flag alias
+    pub alias: Option<Vec<String>>,
+    /// List aliases in help, completion always accepts them
+    pub alias_in_help: bool,
+    /// Environment variable used when the flag is not on the command line
+    pub env: Option<String>,
+    /// Value used when the flag is neither on the command line, in env nor in the config file
+    pub default: Option<String>,
+    /// Fail the run when the flag resolves to no value
+    pub required: bool,
+    /// Only values accepted, compared case sensitively, listed in help
+    pub possible_values: Option<Vec<String>>,
+    /// Check run on the resolved value after its type is checked
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub validator: Option<Validator>,
+}
+
+impl fmt::Debug for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Flag")
+            .field("name", &self.name)
+            .field("description", &self.description)
+            .field("flag_type", &self.flag_type)
+            .field("alias", &self.alias)
+            .field("alias_in_help", &self.alias_in_help)
+            .field("env", &self.env)
+            .field("default", &self.default)
+            .field("required", &self.required)
+            .field("possible_values", &self.possible_values)
+            .field("validator", &self.validator.is_some())
+            .finish()
+    }
+}
+
+/// Value type of a flag
+#[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(rename_all = "lowercase"))]
+pub enum FlagType {
+    Bool,
+    String,
+    Int,
+    Float,
+    /// Repeatable `KEY=VALUE` pairs, read with `Context::map_flag`
+    Map,
+}
+
+/// Where the value of a flag comes from
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum FlagSource {
+    Cli,
+    Env,
+    /// The file set with `App::config_file`
+    Config,
+    Default,
+}
+
+impl fmt::Display for FlagSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FlagSource::Cli => f.write_str("cli"),
+            FlagSource::Env => f.write_str("env"),
+            FlagSource::Config => f.write_str("config"),
+            FlagSource::Default => f.write_str("default"),
+        }
+    }
+}
+
+impl Flag {
+    /// Create new instance of `Flag`
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Flag, FlagType};
+    ///
+    /// let flag = Flag::new("verbose", FlagType::Bool);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// The name is given without its leading dashes.
+    ///
+    /// ```should_panic
+    /// use suihorse::{Flag, FlagType};
+    ///
+    /// let flag = Flag::new("--verbose", FlagType::Bool);
+    /// ```
+    pub fn new<T: Into<String>>(name: T, flag_type: FlagType) -> Self {
+        let name = name.into();
+        if name.starts_with('-') {
+            panic!(r#"Flag name "{}" must not start with "-"."#, name);
+        }
+
+        Self {
+            name,
+            description: None,
+            flag_type,
+            alias: None,
+            alias_in_help: false,
+            env: None,
+            default: None,
+            required: false,
+            possible_values: None,
+            validator: None,
+        }
+    }
+
+    /// Set description of the flag
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Flag, FlagType};
+    ///
+    /// let flag = Flag::new("verbose", FlagType::Bool)
+    ///     .description("print more output");
+    /// ```
+    pub fn description<T: Into<String>>(mut self, description: T) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Set alias of the flag, a single character alias is used as `-v`
+    /// and a longer one as `--verb`
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Flag, FlagType};
+    ///
+    /// let flag = Flag::new("verbose", FlagType::Bool)
+    ///     .alias("v");
+    /// ```
+    pub fn alias<T: Into<String>>(mut self, name: T) -> Self {
+        if let Some(ref mut alias) = self.alias {
+            (*alias).push(name.into());
+        } else {
+            self.alias = Some(vec![name.into()]);
+        }
+        self
+    }
+
+    /// Set short alias of the flag, used as `-o`, same as `alias` with a single character
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("build")
+    ///     .flag(Flag::new("output", FlagType::String).short('o'))
+    ///     .flag(Flag::new("jobs", FlagType::Int).short('n'))
+    ///     .flag(Flag::new("verbose", FlagType::Bool).short('v'))
+    ///     .action(|c| {
+    ///         assert_eq!(c.flag_raw("output"), Some("out/app"));
+    ///         assert_eq!(c.flag_raw("jobs"), Some("4"));
+    ///         assert!(c.bool_flag("verbose"));
+    ///         assert_eq!(c.args, vec!["main.rs"]);
+    ///     });
+    ///
+    /// let args = vec!["-o", "out/app", "main.rs", "-n", "4", "-v"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// The short alias is given without its leading dash.
+    ///
+    /// ```should_panic
+    /// use suihorse::{Flag, FlagType};
+    ///
+    /// let flag = Flag::new("verbose", FlagType::Bool).short('-');
+    /// ```
+    pub fn short(self, short: char) -> Self {
+        if short == '-' {
+            panic!(r#"Short flag of "{}" must not be "-"."#, self.name);
+        }
+        self.alias(short.to_string())
+    }
+
+    /// List the aliases next to the flag name in help, they are hidden by default
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Flag, FlagType};
+    ///
+    /// let flag = Flag::new("verbose", FlagType::Bool)
+    ///     .alias("v")
+    ///     .alias_in_help(true);
+    /// ```
+    pub fn alias_in_help(mut self, alias_in_help: bool) -> Self {
+        self.alias_in_help = alias_in_help;
+        self
+    }
+
+    /// Set environment variable read when the flag is not on the command line
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Flag, FlagType};
+    ///
+    /// let flag = Flag::new("token", FlagType::String)
+    ///     .env("MYAPP_TOKEN");
+    /// ```
+    pub fn env<T: Into<String>>(mut self, name: T) -> Self {
+        self.env = Some(name.into());
+        self
+    }
+
+    /// Set value used when the flag is neither on the command line, in env
+    /// nor in the config file
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Flag, FlagType};
+    ///
+    /// let flag = Flag::new("jobs", FlagType::Int)
+    ///     .default_value("4");
+    /// ```
+    pub fn default_value<T: Into<String>>(mut self, value: T) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+
+    /// Require a value for the flag, from the command line, env or default.
+    /// The run fails with a `MissingRequiredFlag` error holding the usage,
+    /// shown with its message, before the action runs. Required flags are unbracketed in usage.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::error::{ActionError, ActionErrorKind};
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("build")
+    ///     .flag(Flag::new("output", FlagType::String).required(true))
+    ///     .flag(Flag::new("jobs", FlagType::Int))
+    ///     .action(|c| assert_eq!(c.string_flag("output").unwrap(), "out/app"));
+    ///
+    /// let args = vec!["--output", "out/app"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    ///
+    /// let args = vec!["--jobs", "4"];
+    /// let error = command.run_with_result(args.into_iter().map(String::from).collect()).unwrap_err();
+    /// assert_eq!(
+    ///     error.downcast_ref::<ActionError>().unwrap().kind,
+    ///     ActionErrorKind::MissingRequiredFlag {
+    ///         flag: "output".to_string(),
+    ///         usage: Some("build --output <string> [--jobs <int>]".to_string()),
+    ///     }
+    /// );
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     "MissingRequiredFlag: --output is required\nUsage: build --output <string> [--jobs <int>]"
+    /// );
+    /// ```
+    pub fn required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+
+    /// Restrict the flag to the given values, any other value fails the run
+    /// with an `InvalidChoice` error listing them. Values are compared
+    /// case sensitively and listed in the help line of the flag.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::error::{ActionError, ActionErrorKind};
+    /// use suihorse::{Command, Flag, FlagType, Help};
+    ///
+    /// let command = Command::new("export")
+    ///     .flag(
+    ///         Flag::new("format", FlagType::String)
+    ///             .description("output format")
+    ///             .possible_values(&["json", "yaml", "toml"]),
+    ///     )
+    ///     .action(|c| assert_eq!(c.flag_raw("format"), Some("yaml")));
+    ///
+    /// let args = vec!["--format", "yaml"];
+    /// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+    ///
+    /// let args = vec!["--format", "xml"];
+    /// let error = command.run_with_result(args.into_iter().map(String::from).collect()).unwrap_err();
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     r#"InvalidChoice: "xml" isn't a valid value for --format [possible values: json, yaml, toml]"#
+    /// );
+    ///
+    /// let args = vec!["--format", "JSON"];
+    /// let error = command.run_with_result(args.into_iter().map(String::from).collect()).unwrap_err();
+    /// assert_eq!(
+    ///     error.downcast_ref::<ActionError>().unwrap().kind,
+    ///     ActionErrorKind::InvalidChoice(
+    ///         "format".to_string(),
+    ///         "JSON".to_string(),
+    ///         vec!["json".to_string(), "yaml".to_string(), "toml".to_string()]
+    ///     )
+    /// );
+    ///
+    /// assert!(command
+    ///     .help_text()
+    ///     .contains("output format [possible values: json, yaml, toml]"));
+    /// ```
+    pub fn possible_values(mut self, values: &[&str]) -> Self {
+        self.possible_values = Some(values.iter().map(|v| v.to_string()).collect());
+        self
+    }
+
+    /// Set check of the flag value, an error it returns fails the run
+    /// with an `InvalidFlagValue` error naming the flag.
+    /// `Int` and `Float` values are checked to parse first,
+    /// failing with a `Parse` error.
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::error::{ActionError, ActionErrorKind};
+    /// use suihorse::{Command, Flag, FlagType};
+    ///
+    /// let command = Command::new("build")
+    ///     .flag(Flag::new("jobs", FlagType::Int).validator(|value| match value {
+    ///         "0" => Err("must be at least 1".to_string()),
+    ///         _ => Ok(()),
+    ///     }))
+    ///     .action(|_| unreachable!());
+    ///
+    /// let error = command.run_with_result(vec!["--jobs".to_string(), "0".to_string()]).unwrap_err();
+    /// assert_eq!(
+    ///     error.downcast_ref::<ActionError>().unwrap().kind,
+    ///     ActionErrorKind::InvalidFlagValue("jobs".to_string(), "must be at least 1".to_string())
+    /// );
+    ///
+    /// let error = command.run_with_result(vec!["--jobs".to_string(), "abc".to_string()]).unwrap_err();
+    /// assert_eq!(error.to_string(), r#"Parse: invalid value "abc" for flag --jobs at arg 1 (expected integer)"#);
+    /// ```
+    pub fn validator<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&str) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// Check `value` parses as the flag type, failing with a `Parse` error,
+    /// is one of the possible values, failing with an `InvalidChoice` error,
+    /// then run the validator, failing with an `InvalidFlagValue` error
+    pub(crate) fn validate(&self, value: &str) -> Result<(), ActionError> {
+        let expected = match self.flag_type {
+            FlagType::Bool if parse_bool(value).is_none() => Some("bool"),
+            FlagType::Int if value.parse::<i64>().is_err() => Some("integer"),
+            FlagType::Float if value.parse::<f64>().is_err() => Some("number"),
+            FlagType::Map if !value.contains('=') => Some("KEY=VALUE"),
+            _ => None,
+        };
+        if let Some(expected) = expected {
+            return Err(ActionError {
+                kind: ActionErrorKind::Parse {
+                    flag: self.name.clone(),
+                    value: value.to_string(),
+                    expected: expected.to_string(),
+                    index: None,
+                },
+            });
+        }
+
+        if let Some(values) = &self.possible_values {
+            if !values.iter().any(|v| v == value) {
+                return Err(ActionError {
+                    kind: ActionErrorKind::InvalidChoice(
+                        self.name.clone(),
+                        value.to_string(),
+                        values.clone(),
+                    ),
+                });
+            }
+        }
+
+        match &self.validator {
+            Some(validator) => validator(value).map_err(|message| ActionError {
+                kind: ActionErrorKind::InvalidFlagValue(self.name.clone(), message),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Resolve the raw value of the flag and its source, command line first,
+    /// then env, its own variable before the one named after the env prefix,
+    /// then the same variables in the env file, then the config file value
+    /// for the command at `command_path`, then default
+    pub(crate) fn resolve(
+        &self,
+        args: &[String],
+        dispatch: &Dispatch,
+        command_path: &[String],
+    ) -> Option<(String, FlagSource)> {
+        if let Some(value) = self.cli_value(args) {
+            return Some((value, FlagSource::Cli));
+        }
+
+        let prefixed = dispatch.env_prefix.map(|prefix| self.prefixed_env(prefix));
+        let names: Vec<&String> = self.env.iter().chain(prefixed.iter()).collect();
+        let value = names
+            .iter()
+            .find_map(|name| env::var(name).ok())
+            .or_else(|| names.iter().find_map(|name| dispatch.env_file_value(name)));
+        if let Some(value) = value {
+            return Some((value, FlagSource::Env));
+        }
+
+        if let Some(value) = dispatch.config_value(command_path, &self.name) {
+            return Some((value.to_string(), FlagSource::Config));
+        }
+
+        self.default
+            .as_ref()
+            .map(|value| (value.to_owned(), FlagSource::Default))
+    }
+
+    /// Env variable of the flag under `prefix`, `MYAPP_LOG_LEVEL` for `log-level`
+    fn prefixed_env(&self, prefix: &str) -> String {
+        format!("{}_{}", prefix, self.name.to_uppercase().replace('-', "_"))
+    }
+
+    /// Value of the flag on the command line, bool flags don't take one
+    /// and are negated by `--no-<name>`. The last occurrence wins for every flag.
+    fn cli_value(&self, args: &[String]) -> Option<String> {
+        match self.flag_type {
+            FlagType::Bool => {
+                let token = self.token();
+                let alias_tokens = self.alias_tokens();
+                let negated_token = format!("--no-{}", self.name);
+                args.iter().rev().find_map(|arg| {
+                    if arg == &token || alias_tokens.contains(arg) {
+                        Some("true".to_string())
+                    } else if arg == &negated_token {
+                        Some("false".to_string())
+                    } else {
+                        None
+                    }
+                })
+            }
+            _ => {
+                let token = self.token();
+                let alias_tokens = self.alias_tokens();
+                let mut value = None;
+                let mut iter = args.iter();
+                while let Some(arg) = iter.next() {
+                    if arg == &token || alias_tokens.contains(arg) {
+                        // the value is skipped, so it is never taken for the flag
+                        value = iter.next().cloned().or(value);
+                    }
+                }
+                value
+            }
+        }
+    }
+
+    /// Number of times the flag or one of its aliases is in `args`
+    pub(crate) fn occurrences(&self, args: &[String]) -> u64 {
+        let token = self.token();
+        let alias_tokens = self.alias_tokens();
+        args.iter()
+            .filter(|arg| **arg == token || alias_tokens.contains(arg))
+            .count() as u64
+    }
+
+    /// Whether `arg` is this flag, one of its aliases or its `--no-` form
+    pub(crate) fn matches(&self, arg: &str) -> bool {
+        arg == self.token()
+            || self.alias_tokens().iter().any(|a| a == arg)
+            || (self.flag_type == FlagType::Bool && arg == format!("--no-{}", self.name))
+    }
+
+    /// Whether the flag consumes the next arg as its value
+    pub(crate) fn takes_value(&self) -> bool {
+        self.flag_type != FlagType::Bool
+    }
+
+    /// Canonical form of the flag as typed on the command line
+    pub(crate) fn token(&self) -> String {
+        format!("--{}", self.name)
+    }
+
+    /// Aliases of the flag as typed on the command line
+    pub(crate) fn alias_tokens(&self) -> Vec<String> {
+        match &self.alias {
+            Some(alias) => alias
+                .iter()
+                .map(|a| match a.chars().count() {
+                    1 => format!("-{}", a),
+                    _ => format!("--{}", a),
+                })
+                .collect(),
+            None => vec![],
+        }
+    }
+}
@@ -1,7 +1,531 @@
-pub(crate) trait Help {
+use crate::{Command, Flag, FlagType};
+use std::env;
+use std::path::Path;
+
+/// Help text of `App` and `Command`
+///
+/// Example
+///
+/// ```
+/// use suihorse::{App, Command, Flag, FlagType, Help};
+///
+/// let command = Command::new("build")
+///     .description("build the package")
+///     .flag(Flag::new("release", FlagType::Bool).alias("r"));
+/// assert!(command.help_text().contains("--release"));
+/// assert!(!command.help_text().contains("-r,"));
+///
+/// let app = App::new("cli")
+///     .command(command);
+/// assert!(app.help_text().contains("build the package"));
+/// ```
+///
+/// Descriptions are wrapped to the terminal width read from `COLUMNS`, 80 by default
+///
+/// ```
+/// use std::env;
+/// use suihorse::{Command, Flag, FlagType, Help};
+///
+/// env::set_var("COLUMNS", "50");
+/// let command = Command::new("build")
+///     .flag(Flag::new("release", FlagType::Bool)
+///         .description("build with optimizations and without debug assertions"));
+///
+/// assert!(command.help_text().contains(concat!(
+///     "\t--release  : build with optimizations and\n",
+///     "\t             without debug assertions\n",
+/// )));
+/// ```
+///
+/// Implement it on your own types to render help your way,
+/// `help` prints whatever `help_text` returns
+///
+/// ```
+/// use suihorse::{Command, Help};
+///
+/// struct Plugin(Command);
+///
+/// impl Help for Plugin {
+///     fn help_text(&self) -> String {
+///         format!("plugin {}\n\n{}", self.0.name, self.0.help_text())
+///     }
+/// }
+///
+/// let plugin = Plugin(Command::new("lint").description("lint the sources"));
+/// assert!(plugin.help_text().starts_with("plugin lint\n\nDescription:\n\tlint the sources"));
+/// plugin.help();
+/// plugin.diagnostic_help();
+/// ```
+///
+/// These methods are stable. New methods may be added with a default body,
+/// so implementing the trait never breaks on upgrade. The layout of the
+/// `App` and `Command` help text itself is not stable, compare with
+/// `contains` rather than whole strings.
+pub trait Help {
+    /// Render the help text
     fn help_text(&self) -> String;
 
+    /// Print the help text to stdout, when it was asked for
     fn help(&self) {
         println!("{}", self.help_text());
     }
+
+    /// Print the help text to stderr, as a guide after an error,
+    /// keeping stdout for the real output of a pipeline
+    fn diagnostic_help(&self) {
+        eprintln!("{}", self.help_text());
+    }
+}
+
+/// Tokens triggering help unless `App::help_flags` overrides them
+pub(crate) const DEFAULT_HELP_FLAGS: [&str; 2] = ["-h", "--help"];
+
+/// Tokens printing the version when `App::version` is set
+pub(crate) const VERSION_FLAGS: [&str; 2] = ["--version", "-V"];
+
+/// Width assumed for a tab when wrapping help
+const TAB_WIDTH: usize = 8;
+
+/// Narrowest description column worth wrapping to
+const MIN_DESCRIPTION_WIDTH: usize = 20;
+
+/// Terminal width from `COLUMNS`, 80 when unset or invalid
+fn terminal_width() -> usize {
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.trim().parse().ok())
+        .filter(|columns| *columns > 0)
+        .unwrap_or(80)
+}
+
+/// Split `text` into lines of at most `width` chars on whitespace,
+/// a longer word gets a line of its own
+fn wrapped_lines(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(line);
+            line = String::new();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line += word;
+    }
+    lines.push(line);
+
+    lines
+}
+
+/// Render `(name, description)` rows with descriptions aligned after the longest name.
+/// Descriptions are wrapped to the terminal width, continuing under the description
+/// column, unless it leaves them too narrow.
+fn aligned_text(rows: &[(String, String)]) -> String {
+    let mut text = String::new();
+
+    let name_max_len = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    let indent = name_max_len + " : ".len();
+    let description_width = terminal_width().saturating_sub(TAB_WIDTH + indent);
+
+    for (name, description) in rows.iter() {
+        let lines = if description_width < MIN_DESCRIPTION_WIDTH
+            || description.chars().count() <= description_width
+        {
+            vec![description.to_string()]
+        } else {
+            wrapped_lines(description, description_width)
+        };
+
+        text += &format!(
+            "\t{} {}: {}\n",
+            name,
+            " ".repeat(name_max_len - name.len()),
+            lines[0]
+        );
+        for line in &lines[1..] {
+            text += &format!("\t{}{}\n", " ".repeat(indent), line);
+        }
+    }
+
+    text
+}
+
+/// Placeholder shown after a flag taking a value, such as ` <int>`
+pub(crate) fn value_placeholder(flag: &Flag) -> &'static str {
+    match flag.flag_type {
+        FlagType::Bool => "",
+        FlagType::String => " <string>",
+        FlagType::Int => " <int>",
+        FlagType::Float => " <float>",
+        FlagType::Map => " <key=value>",
+    }
+}
+
+/// Words invoking a command below `parents`: the binary name in `program_name`,
+/// if any, followed by the parent command names
+pub(crate) fn usage_prefix(program_name: &str, parents: &[String]) -> Vec<String> {
+    let mut words: Vec<String> = vec![];
+
+    if let Some(bin_name) = Path::new(program_name).file_name() {
+        words.push(bin_name.to_string_lossy().into_owned());
+    }
+    words.extend(parents.iter().cloned());
+
+    words
+}
+
+/// Usage word of a flag, such as `[--jobs <int>]`, unbracketed when required
+fn flag_usage(f: &Flag) -> String {
+    let word = format!("{}{}", f.token(), value_placeholder(f));
+    if f.required {
+        word
+    } else {
+        format!("[{}]", word)
+    }
+}
+
+/// Usage of an app named `name` synthesized from its commands and `flags`,
+/// ending with `[arg]...` when `takes_args`, as its action does
+pub(crate) fn generated_app_usage(
+    name: &str,
+    has_commands: bool,
+    flags: &[&Flag],
+    takes_args: bool,
+) -> String {
+    let mut words = vec![name.to_string()];
+
+    if has_commands {
+        words.push("[command]".to_string());
+    }
+    words.extend(flags.iter().map(|f| flag_usage(f)));
+    if takes_args {
+        words.push("[arg]...".to_string());
+    }
+
+    words.join(" ")
+}
+
+/// Usage of `command` synthesized from the binary name in `program_name`,
+/// the names of its parent commands, its sub commands, flags and positional args
+pub(crate) fn generated_usage(program_name: &str, parents: &[String], command: &Command) -> String {
+    let mut words = usage_prefix(program_name, parents);
+    words.push(command.name.clone());
+
+    if command.commands.is_some() {
+        words.push("[command]".to_string());
+    }
+    words.extend(command.flags.iter().flatten().map(flag_usage));
+    for name in command.args.iter().flatten() {
+        words.push(format!("<{}>", name));
+    }
+    if let Some(name) = &command.variadic_arg {
+        words.push(format!("[{}]...", name));
+    }
+
+    words.join(" ")
+}
+
+/// Rows of the `Flags:` section, in declaration order unless `sort`, followed by
+/// the `help_flags` and by version when `version_flag` and no flag is named so
+fn flag_rows(
+    flags: &[Flag],
+    sort: bool,
+    help_flags: &[String],
+    version_flag: bool,
+) -> Vec<(String, String)> {
+    let mut flags: Vec<&Flag> = flags.iter().collect();
+    if sort {
+        flags.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+
+    let mut rows: Vec<(String, String)> = flags
+        .iter()
+        .map(|f| {
+            let mut flag_name = f.token();
+            if f.alias_in_help {
+                let mut names = f.alias_tokens();
+                names.push(flag_name);
+                flag_name = names.join(", ");
+            }
+
+            flag_name += value_placeholder(f);
+
+            let mut description = f.description.clone().unwrap_or_default();
+            if let Some(values) = &f.possible_values {
+                if !description.is_empty() {
+                    description += " ";
+                }
+                description += &format!("[possible values: {}]", values.join(", "));
+            }
+
+            (flag_name, description)
+        })
+        .collect();
+    if !help_flags.is_empty() {
+        rows.push((help_flags.join(", "), "Print help".to_string()));
+    }
+    if version_flag && !flags.iter().any(|f| VERSION_FLAGS.iter().any(|token| f.matches(token))) {
+        rows.push(("-V, --version".to_string(), "Print version".to_string()));
+    }
+
+    rows
+}
+
+/// Rows of the `Commands:` section, followed by the rows of
+/// a section per category in order of first appearance
+fn command_sections(commands: &[Command]) -> Vec<(String, Vec<(String, String)>)> {
+    let mut categories: Vec<Option<&str>> = vec![];
+    if commands.is_empty() || commands.iter().any(|c| c.category.is_none()) {
+        categories.push(None);
+    }
+    for c in commands.iter() {
+        if let Some(category) = &c.category {
+            if !categories.contains(&Some(category.as_str())) {
+                categories.push(Some(category));
+            }
+        }
+    }
+
+    categories
+        .into_iter()
+        .map(|category| {
+            let rows: Vec<(String, String)> = commands
+                .iter()
+                .filter(|c| c.category.as_deref() == category)
+                .map(|c| {
+                    let command_name = if let Some(alias) = &c.alias {
+                        format!("{}, {}", alias.join(", "), c.name)
+                    } else {
+                        c.name.clone()
+                    };
+
+                    let mut description = c.description.clone().unwrap_or_default();
+                    if c.deprecated.is_some() {
+                        description =
+                            format!("(deprecated) {}", description).trim_end().to_string();
+                    }
+
+                    (command_name, description)
+                })
+                .collect();
+
+            (category.unwrap_or("Commands").to_string(), rows)
+        })
+        .collect()
+}
+
+/// Part of a help text, rendered by `HelpBuilder::render`
+#[derive(Clone, Debug)]
+enum Section {
+    /// Untitled paragraph
+    Text(String),
+    /// Titled lines, each on a line of its own
+    Lines(String, Vec<String>),
+    /// Titled `(name, description)` rows, descriptions aligned
+    Rows(String, Vec<(String, String)>),
+}
+
+/// Help text built section by section, rendered with the layout
+/// of the `App` and `Command` help: the same headings, indentation
+/// and alignment, descriptions wrapped to the terminal width.
+/// Sections are rendered in the order they are added.
+///
+/// Example
+///
+/// ```
+/// use suihorse::{Command, Flag, FlagType, HelpBuilder};
+///
+/// std::env::set_var("COLUMNS", "80");
+/// let text = HelpBuilder::new()
+///     .description("manage remotes")
+///     .usage("cli remote [command]")
+///     .examples(&["cli remote add origin https://example.com/repo.git"])
+///     .commands(&[Command::new("add").description("add a remote"), Command::new("rm")])
+///     .flags(&[Flag::new("verbose", FlagType::Bool).description("print more")], false)
+///     .render();
+///
+/// assert_eq!(text, concat!(
+///     "Description:\n\tmanage remotes\n\n",
+///     "Usage:\n\tcli remote [command]\n\n",
+///     "Examples:\n\tcli remote add origin https://example.com/repo.git\n\n",
+///     "\nCommands:\n\tadd : add a remote\n\trm  : \n",
+///     "\nFlags:\n\t--verbose  : print more\n\t-h, --help : Print help\n",
+/// ));
+/// ```
+///
+/// Custom sections compose with the built in ones, and headings may be colored
+///
+/// ```
+/// use suihorse::HelpBuilder;
+///
+/// let text = HelpBuilder::new()
+///     .text("deploy tool")
+///     .section("Environment", "DEPLOY_TOKEN")
+///     .color(true)
+///     .render();
+///
+/// assert_eq!(text, "deploy tool\n\n\x1b[1mEnvironment:\x1b[0m\n\tDEPLOY_TOKEN\n\n");
+/// ```
+///
+/// `App` and `Command` render their help with it
+///
+/// ```
+/// use suihorse::{App, Command, Flag, FlagType, Help};
+///
+/// std::env::set_var("COLUMNS", "80");
+/// let command = Command::new("add")
+///     .description("add a remote")
+///     .usage("cli remote add <name> <url>")
+///     .flag(Flag::new("force", FlagType::Bool).alias("f").alias_in_help(true));
+/// assert_eq!(command.help_text(), concat!(
+///     "Description:\n\tadd a remote\n\n",
+///     "Usage:\n\tcli remote add <name> <url>\n\n",
+///     "\nFlags:\n\t-f, --force : \n\t-h, --help  : Print help\n",
+/// ));
+///
+/// let app = App::new("cli")
+///     .description("remote manager")
+///     .command(command.category("Remotes"))
+///     .command(Command::new("init"));
+/// assert_eq!(app.help_text(), concat!(
+///     "cli\nremote manager\n\n",
+///     "Usage:\n\tcli [command]\n\n",
+///     "\nCommands:\n\tinit : \n",
+///     "\nRemotes:\n\tadd : add a remote\n",
+/// ));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct HelpBuilder {
+    sections: Vec<Section>,
+    color: bool,
+    help_flags: Option<Vec<String>>,
+    version_flag: bool,
+}
+
+impl HelpBuilder {
+    /// Create new instance of `HelpBuilder`, without sections
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an untitled paragraph, such as the app description
+    pub fn text<T: Into<String>>(mut self, text: T) -> Self {
+        self.sections.push(Section::Text(text.into()));
+        self
+    }
+
+    /// Add a section titled `title` with a single line
+    pub fn section<T: Into<String>, U: Into<String>>(mut self, title: T, line: U) -> Self {
+        self.sections.push(Section::Lines(title.into(), vec![line.into()]));
+        self
+    }
+
+    /// Add the `Description:` section
+    pub fn description<T: Into<String>>(self, description: T) -> Self {
+        self.section("Description", description)
+    }
+
+    /// Add the `Usage:` section
+    pub fn usage<T: Into<String>>(self, usage: T) -> Self {
+        self.section("Usage", usage)
+    }
+
+    /// Add the `Examples:` section, an example per line
+    pub fn examples<T: AsRef<str>>(mut self, examples: &[T]) -> Self {
+        let lines = examples.iter().map(|e| e.as_ref().to_string()).collect();
+        self.sections.push(Section::Lines("Examples".to_string(), lines));
+        self
+    }
+
+    /// Add a section titled `title` of `(name, description)` rows,
+    /// descriptions aligned after the longest name
+    pub fn rows<T: Into<String>>(mut self, title: T, rows: Vec<(String, String)>) -> Self {
+        self.sections.push(Section::Rows(title.into(), rows));
+        self
+    }
+
+    /// Add the `Commands:` section, followed by a section per command category
+    pub fn commands(self, commands: &[Command]) -> Self {
+        command_sections(commands)
+            .into_iter()
+            .fold(self, |builder, (title, rows)| builder.rows(title, rows))
+    }
+
+    /// Add the `Flags:` section followed by the help flags, none without flags.
+    /// Flags are listed in declaration order unless `sort`.
+    pub fn flags(self, flags: &[Flag], sort: bool) -> Self {
+        if flags.is_empty() {
+            return self;
+        }
+        let default_help_flags: Vec<String> =
+            DEFAULT_HELP_FLAGS.iter().map(|f| f.to_string()).collect();
+        let help_flags = self.help_flags.as_ref().unwrap_or(&default_help_flags);
+        let rows = flag_rows(flags, sort, help_flags, self.version_flag);
+        self.rows("Flags", rows)
+    }
+
+    /// List `help_flags` as the help row of the `Flags:` sections added next,
+    /// `-h, --help` by default
+    ///
+    /// Example
+    ///
+    /// ```
+    /// use suihorse::{Flag, FlagType, HelpBuilder};
+    ///
+    /// let text = HelpBuilder::new()
+    ///     .help_flags(&["-?", "--hilfe"])
+    ///     .flags(&[Flag::new("verbose", FlagType::Bool)], false)
+    ///     .render();
+    /// assert!(text.contains("\t-?, --hilfe : Print help\n"));
+    /// ```
+    pub fn help_flags<T: AsRef<str>>(mut self, help_flags: &[T]) -> Self {
+        self.help_flags = Some(help_flags.iter().map(|f| f.as_ref().to_string()).collect());
+        self
+    }
+
+    /// List `-V, --version` after help in the `Flags:` sections added next,
+    /// off by default
+    pub fn version_flag(mut self, version_flag: bool) -> Self {
+        self.version_flag = version_flag;
+        self
+    }
+
+    /// Render headings in bold with ANSI escape codes, off by default
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Render the sections into the help text
+    pub fn render(&self) -> String {
+        let heading = |title: &str| {
+            if self.color {
+                format!("\x1b[1m{}:\x1b[0m", title)
+            } else {
+                format!("{}:", title)
+            }
+        };
+
+        let mut text = String::new();
+        for section in &self.sections {
+            match section {
+                Section::Text(paragraph) => text += &format!("{}\n\n", paragraph),
+                Section::Lines(title, lines) => {
+                    text += &format!("{}\n", heading(title));
+                    for line in lines {
+                        text += &format!("\t{}\n", line);
+                    }
+                    text += "\n";
+                }
+                Section::Rows(title, rows) => {
+                    text += &format!("\n{}\n", heading(title));
+                    text += &aligned_text(rows);
+                }
+            }
+        }
+
+        text
+    }
 }
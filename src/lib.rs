@@ -1,8 +1,17 @@
 mod app;
 mod command;
+mod completion;
+#[cfg(feature = "config-file")]
+mod config_file;
+mod context;
 pub mod error;
+mod flag;
 mod help;
+mod macros;
+mod parse;
 
-pub use app::{App, Action};
-pub use command::Command;
-use help::Help;
+pub use app::{App, Action, Invocation};
+pub use command::{Command, Outcome};
+pub use context::Context;
+pub use flag::{Flag, FlagSource, FlagType, Validator};
+pub use help::{Help, HelpBuilder};
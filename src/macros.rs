@@ -0,0 +1,70 @@
+/// Build a fallible action taking the positional args as typed params.
+/// Each arg is parsed with `FromStr` in order, a wrong count or a value
+/// failing to parse returns an `InvalidArgs` error naming the param.
+///
+/// Example
+///
+/// ```
+/// use suihorse::error::{ActionError, ActionErrorKind};
+/// use suihorse::{action_fn, Command};
+///
+/// let command = Command::new("resize")
+///     .action_with_result(action_fn!(|file: String, width: u32| {
+///         assert_eq!(file, "logo.png");
+///         assert_eq!(width, 640);
+///     }));
+///
+/// let args = vec!["logo.png", "640"];
+/// command.run_with_result(args.into_iter().map(String::from).collect()).unwrap();
+///
+/// let args = vec!["logo.png", "wide"];
+/// let error = command.run_with_result(args.into_iter().map(String::from).collect()).unwrap_err();
+/// assert_eq!(
+///     error.to_string(),
+///     r#"InvalidArgs: <width> "wide" is invalid: invalid digit found in string"#
+/// );
+///
+/// let args = vec!["logo.png"];
+/// let error = command.run_with_result(args.into_iter().map(String::from).collect()).unwrap_err();
+/// assert_eq!(
+///     error.downcast_ref::<ActionError>().unwrap().kind,
+///     ActionErrorKind::InvalidArgs("expected 2 args <file> <width>, got 1".to_string())
+/// );
+/// ```
+#[macro_export]
+macro_rules! action_fn {
+    (|$($name:ident : $ty:ty),* $(,)?| $body:expr) => {
+        move |c: &$crate::Context| -> ::std::result::Result<(), ::std::boxed::Box<dyn ::std::error::Error>> {
+            let names: &[&str] = &[$(concat!("<", stringify!($name), ">")),*];
+            if c.args.len() != names.len() {
+                return ::std::result::Result::Err(::std::boxed::Box::new($crate::error::ActionError {
+                    kind: $crate::error::ActionErrorKind::InvalidArgs(format!(
+                        "expected {} args {}, got {}",
+                        names.len(),
+                        names.join(" "),
+                        c.args.len()
+                    )),
+                }));
+            }
+
+            #[allow(unused_mut, unused_variables)]
+            let mut args = c.args.iter();
+            $(
+                let $name: $ty = {
+                    let arg = args.next().unwrap();
+                    arg.parse::<$ty>().map_err(|e| $crate::error::ActionError {
+                        kind: $crate::error::ActionErrorKind::InvalidArgs(format!(
+                            "<{}> {:?} is invalid: {}",
+                            stringify!($name),
+                            arg,
+                            e
+                        )),
+                    })?
+                };
+            )*
+
+            $body;
+            ::std::result::Result::Ok(())
+        }
+    };
+}
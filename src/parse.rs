@@ -0,0 +1,292 @@
+use crate::error::{ActionError, ActionErrorKind};
+use crate::flag::parse_bool;
+use crate::{Flag, FlagType};
+use std::fs;
+
+/// Deepest nesting of response files read from other response files
+const MAX_RESPONSE_FILE_DEPTH: usize = 8;
+
+/// Replace each `@path` arg before a standalone "--" by the whitespace separated
+/// args read from `path`, which may themselves be response files
+pub(crate) fn expanded_response_files(
+    raw_args: &[String],
+    depth: usize,
+) -> Result<Vec<String>, ActionError> {
+    let mut args = vec![];
+
+    let mut iter = raw_args.iter();
+    for arg in iter.by_ref() {
+        if arg == "--" {
+            args.push(arg.to_owned());
+            break;
+        }
+
+        let path = match arg.strip_prefix('@') {
+            Some(path) if !path.is_empty() => path,
+            _ => {
+                args.push(arg.to_owned());
+                continue;
+            }
+        };
+
+        let response_file_error = |reason: String| ActionError {
+            kind: ActionErrorKind::ResponseFile(path.to_string(), reason),
+        };
+        if depth >= MAX_RESPONSE_FILE_DEPTH {
+            return Err(response_file_error(format!(
+                "nested deeper than {} response files",
+                MAX_RESPONSE_FILE_DEPTH
+            )));
+        }
+        let content = fs::read_to_string(path).map_err(|e| response_file_error(e.to_string()))?;
+        let file_args: Vec<String> = content.split_whitespace().map(String::from).collect();
+        args.extend(expanded_response_files(&file_args, depth + 1)?);
+    }
+    args.extend(iter.cloned());
+
+    Ok(args)
+}
+
+/// Trim surrounding whitespace from each arg before a standalone "--",
+/// dropping the ones left empty. The arg after a value-taking flag of `flags`
+/// and args after "--" are kept verbatim, so `--name ""` stays an empty value.
+pub(crate) fn trimmed_args(raw_args: &[String], flags: &[&Flag]) -> Vec<String> {
+    let (leading, trailing) = match raw_args.iter().position(|arg| arg == "--") {
+        Some(index) => (&raw_args[..index], &raw_args[index..]),
+        None => (raw_args, &[][..]),
+    };
+
+    let mut args = vec![];
+    let mut is_value = false;
+    for arg in leading {
+        if is_value {
+            is_value = false;
+            args.push(arg.to_owned());
+            continue;
+        }
+        let arg = arg.trim();
+        is_value = flags.iter().any(|f| f.takes_value() && f.matches(arg));
+        if !arg.is_empty() {
+            args.push(arg.to_string());
+        }
+    }
+    args.extend(trailing.iter().cloned());
+    args
+}
+
+/// Whether `arg` is `--flag=value` for a bool flag, read by `bool_assignments`
+fn is_bool_assignment(arg: &str, flags: &[&Flag]) -> bool {
+    arg.split_once('=').is_some_and(|(name, _)| {
+        flags
+            .iter()
+            .any(|f| f.flag_type == FlagType::Bool && f.matches(name))
+    })
+}
+
+/// Translate `--flag=value` of bool flags, kept whole by `split_args`,
+/// --verbose=true => "--verbose", --verbose=false => "--no-verbose",
+/// so the value is never taken for a positional arg. A value that is not a
+/// bool fails with a `Parse` error holding its position in `args`.
+pub(crate) fn bool_assignments(args: &[String], flags: &[&Flag]) -> Result<Vec<String>, ActionError> {
+    args.iter()
+        .enumerate()
+        .map(|(index, arg)| {
+            let assignment = arg.split_once('=').and_then(|(name, value)| {
+                let f = flags
+                    .iter()
+                    .find(|f| f.flag_type == FlagType::Bool && f.matches(name))?;
+                Some((f, name, value))
+            });
+            let (f, name, value) = match assignment {
+                Some(assignment) => assignment,
+                None => return Ok(arg.to_owned()),
+            };
+            let value = parse_bool(value).ok_or_else(|| ActionError {
+                kind: ActionErrorKind::Parse {
+                    flag: f.name.clone(),
+                    value: value.to_string(),
+                    expected: "bool".to_string(),
+                    index: Some(index),
+                },
+            })?;
+            // `--no-verbose=true` negates as `--no-verbose` does
+            if value == (name == format!("--no-{}", f.name)) {
+                Ok(format!("--no-{}", f.name))
+            } else {
+                Ok(f.token())
+            }
+        })
+        .collect()
+}
+
+/// Normalized args before a standalone "--", the only ones holding flags
+pub(crate) fn flag_args(raw_args: &[String], flags: &[&Flag]) -> Vec<String> {
+    split_args(raw_args, flags).0
+}
+
+/// Split arg with "=" to unify arg notations.
+/// --flag=value => ["--flag", "value"]
+/// --flag value => ["--flag", "value"]
+/// Bool `flags` given a value are kept whole for `bool_assignments`.
+/// Everything after a standalone "--" is kept verbatim, minus the "--",
+/// and returned separately from the normalized args before it.
+pub(crate) fn split_args(raw_args: &[String], flags: &[&Flag]) -> (Vec<String>, Vec<String>) {
+    let (leading, trailing) = match raw_args.iter().position(|arg| arg == "--") {
+        Some(index) => (&raw_args[..index], &raw_args[index + 1..]),
+        None => (raw_args, &[][..]),
+    };
+
+    let args = leading.iter().fold(Vec::<String>::new(), |mut acc, cur| {
+        if cur.starts_with('-') && cur.contains('=') && !is_bool_assignment(cur, flags) {
+            let mut splitted_flag: Vec<String> =
+                cur.splitn(2, '=').map(|s| s.to_owned()).collect();
+            acc.append(&mut splitted_flag);
+        } else {
+            acc.push(cur.to_owned());
+        }
+        acc
+    });
+
+    (args, trailing.to_vec())
+}
+
+/// Expand clustered short flags, -abc => ["-a", "-b", "-c"],
+/// only when every character is the alias of a bool flag.
+/// The value of a flag is kept as is even when it starts with `-`.
+pub(crate) fn expanded_short_flags(args: Vec<String>, flags: &[&Flag]) -> Vec<String> {
+    let is_bool_alias = |c: char| {
+        flags.iter().any(|f| {
+            f.flag_type == FlagType::Bool
+                && f.alias
+                    .iter()
+                    .flatten()
+                    .any(|a| a.chars().eq(std::iter::once(c)))
+        })
+    };
+
+    let mut expanded = vec![];
+    let mut is_value = false;
+    for arg in args {
+        if is_value {
+            is_value = false;
+            expanded.push(arg);
+            continue;
+        }
+        is_value = flags.iter().any(|f| f.takes_value() && f.matches(&arg));
+
+        match arg.strip_prefix('-') {
+            Some(cluster)
+                if cluster.chars().count() > 1
+                    && !cluster.starts_with('-')
+                    && cluster.chars().all(is_bool_alias) =>
+            {
+                expanded.extend(cluster.chars().map(|c| format!("-{}", c)));
+            }
+            _ => expanded.push(arg),
+        }
+    }
+    expanded
+}
+
+/// Translate Windows style flags, /name => "--name", /name:value => ["--name", "value"],
+/// when `name` is the name or an alias of a declared flag. Other args starting
+/// with "/", such as paths, are kept as is.
+pub(crate) fn windows_style_flags(args: Vec<String>, flags: &[&Flag]) -> Vec<String> {
+    let token_of = |name: &str| {
+        flags.iter().find_map(|f| {
+            if f.name == name {
+                return Some(f.token());
+            }
+            let index = f.alias.iter().flatten().position(|a| a == name)?;
+            f.alias_tokens().into_iter().nth(index)
+        })
+    };
+
+    args.into_iter()
+        .flat_map(|arg| {
+            let translated = arg.strip_prefix('/').and_then(|flag| {
+                let (name, value) = match flag.split_once(':') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (flag, None),
+                };
+                let mut tokens = vec![token_of(name)?];
+                tokens.extend(value.map(String::from));
+                Some(tokens)
+            });
+            translated.unwrap_or_else(|| vec![arg])
+        })
+        .collect()
+}
+
+/// Expand unique prefixes of long flags, --verb => "--verbose",
+/// failing with the candidates on an ambiguous prefix.
+/// Exact matches, short flags and flag values are kept as is.
+pub(crate) fn expanded_abbreviations(
+    args: Vec<String>,
+    flags: &[&Flag],
+) -> Result<Vec<String>, ActionError> {
+    let long_tokens: Vec<String> = flags
+        .iter()
+        .flat_map(|f| {
+            let mut tokens = vec![f.token()];
+            tokens.extend(f.alias_tokens().into_iter().filter(|a| a.starts_with("--")));
+            if f.flag_type == FlagType::Bool {
+                tokens.push(format!("--no-{}", f.name));
+            }
+            tokens
+        })
+        .collect();
+
+    let mut expanded = vec![];
+    let mut is_value = false;
+    for arg in args {
+        if is_value || !arg.starts_with("--") || arg == "--" || long_tokens.contains(&arg) {
+            is_value = !is_value && flags.iter().any(|f| f.takes_value() && f.matches(&arg));
+            expanded.push(arg);
+            continue;
+        }
+
+        let candidates: Vec<&String> = long_tokens.iter().filter(|t| t.starts_with(&arg)).collect();
+        match candidates.as_slice() {
+            [] => expanded.push(arg),
+            [token] => {
+                is_value = flags.iter().any(|f| f.takes_value() && f.matches(token));
+                expanded.push(token.to_string());
+            }
+            _ => {
+                return Err(ActionError {
+                    kind: ActionErrorKind::AmbiguousFlag(
+                        arg,
+                        candidates.into_iter().cloned().collect(),
+                    ),
+                })
+            }
+        }
+    }
+
+    Ok(expanded)
+}
+
+/// Parse `KEY=VALUE` lines of an env file, skipping blank lines and comments.
+/// Values may be wrapped in single or double quotes.
+pub(crate) fn env_file_vars(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line.split_once('=')?;
+            let value = value.trim();
+            let value = match value.chars().next() {
+                Some(quote @ ('"' | '\'')) => value
+                    .strip_prefix(quote)
+                    .and_then(|v| v.rfind(quote).map(|end| &v[..end]))
+                    .unwrap_or(value),
+                // unquoted values end at an inline comment
+                _ => value.split(" #").next().unwrap_or(value).trim_end(),
+            };
+            Some((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}